@@ -0,0 +1,219 @@
+use std::{
+    env, fmt,
+    path::{Path, PathBuf},
+    process,
+};
+
+use fxhash::FxHashMap;
+use serde::Deserialize;
+use serde_value::Value;
+
+use elfo_core as elfo;
+
+use elfo::{config::AnyConfig, Topology};
+
+use crate::{
+    config::Config, find_unknown_sections, get_config, source::ConfigSource, ReloadConfigsError,
+};
+
+const CHECK_CONFIG_ARG: &str = "--check-config";
+
+/// The result of the offline config validation.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct CheckConfigReport {
+    pub errors: Vec<ReloadConfigsError>,
+    /// Sections that match no actor group.
+    pub unknown_sections: Vec<String>,
+}
+
+impl CheckConfigReport {
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+impl fmt::Display for CheckConfigReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for section in &self.unknown_sections {
+            writeln!(f, "warning: section `{}` matches no actor group", section)?;
+        }
+
+        if self.is_ok() {
+            return writeln!(f, "config is valid");
+        }
+
+        writeln!(f, "config is invalid:")?;
+        for error in &self.errors {
+            writeln!(f, "  {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Validates configs against the topology without starting any actors.
+///
+/// Files are loaded in the same way as `from_paths` does it. Then, sections
+/// of all groups are decoded in the same way as on `ValidateConfig`.
+/// Entrypoints are checked too, because they get their configs from the same
+/// files at startup and on reloading.
+///
+/// All rejections are collected, not only the first one. Sections matching
+/// no group are reported, but fail the check only if `deny_unknown_sections`
+/// is set in the configurer's section, e.g. `[system.configurers]`.
+pub fn check_config<P: AsRef<Path>>(
+    topology: &Topology,
+    paths_to_configs: impl IntoIterator<Item = P>,
+) -> CheckConfigReport {
+    let paths = paths_to_configs
+        .into_iter()
+        .map(|path| path.as_ref().to_path_buf())
+        .collect();
+
+    let mut report = CheckConfigReport::default();
+
+    let config = match ConfigSource::Files(paths).load(&mut Vec::new()) {
+        Ok(config) => config,
+        Err(reason) => {
+            report.errors.push(ReloadConfigsError {
+                group: None,
+                reason,
+            });
+            return report;
+        }
+    };
+
+    report.unknown_sections = find_unknown_sections(topology, &config);
+
+    let configs: FxHashMap<String, Value> = Deserialize::deserialize(config).unwrap_or_default();
+
+    // The configurer isn't running during the check, so its group is found
+    // by the config type. Invalid configs are reported below with others.
+    let own_config = topology
+        .actor_groups()
+        .find(|group| group.has_config::<Config>())
+        .and_then(|group| get_config(&configs, &group.name))
+        .and_then(|config| Config::deserialize(config).ok())
+        .unwrap_or_default();
+
+    if own_config.deny_unknown_sections {
+        let errors = report
+            .unknown_sections
+            .iter()
+            .map(|section| ReloadConfigsError {
+                group: None,
+                reason: format!("unknown section `{}`", section),
+            });
+        report.errors.extend(errors);
+    }
+
+    for group in topology.actor_groups() {
+        let config = get_config(&configs, &group.name);
+        let config = config.map_or_else(AnyConfig::default, AnyConfig::new);
+
+        if let Err(reason) = group.validate_config(&config) {
+            report.errors.push(ReloadConfigsError {
+                group: Some(group.name),
+                reason,
+            });
+        }
+    }
+
+    report
+}
+
+/// Handles the `--check-config <path>...` CLI option.
+///
+/// If the option is passed, checks configs, prints the report and exits
+/// the process with the corresponding code. Otherwise, does nothing.
+/// It's intended to be called right after building the topology:
+/// ```ignore
+/// elfo::configurer::check_config_by_args(&topology);
+/// elfo::start(topology).await;
+/// ```
+pub fn check_config_by_args(topology: &Topology) {
+    let paths = ward!(parse_args(env::args().skip(1)));
+
+    if paths.is_empty() {
+        eprintln!("{} requires at least one path", CHECK_CONFIG_ARG);
+        process::exit(2);
+    }
+
+    let report = check_config(topology, paths);
+
+    if report.is_ok() {
+        print!("{}", report);
+        process::exit(0);
+    } else {
+        eprint!("{}", report);
+        process::exit(1);
+    }
+}
+
+/// Returns paths following `--check-config` up to the next option.
+fn parse_args(args: impl Iterator<Item = String>) -> Option<Vec<PathBuf>> {
+    let mut args = args.skip_while(|arg| arg != CHECK_CONFIG_ARG);
+    args.next()?;
+
+    Some(
+        args.take_while(|arg| !arg.starts_with("--"))
+            .map(PathBuf::from)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use elfo::ActorGroup;
+
+    fn parse(args: &[&str]) -> Option<Vec<PathBuf>> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_args_should_collect_paths() {
+        assert_eq!(parse(&["--foo"]), None);
+        assert_eq!(parse(&["--check-config"]), Some(vec![]));
+        assert_eq!(
+            parse(&["--foo", "--check-config", "a.toml", "b.toml", "--bar", "c"]),
+            Some(vec![PathBuf::from("a.toml"), PathBuf::from("b.toml")])
+        );
+    }
+
+    #[test]
+    fn check_config_should_deny_unknown_sections_if_strict() {
+        let topology = Topology::empty();
+        let schema = ActorGroup::new().exec(|_| async {});
+        topology.local("producers").mount(schema);
+        let configurers = topology.local("custom.configurers");
+        configurers.mount(crate::fixture(&topology, AnyConfig::default()));
+
+        let path = env::temp_dir().join(format!("elfo-check-{}.toml", process::id()));
+        let check = |content: &str| {
+            std::fs::write(&path, content).unwrap();
+            check_config(&topology, std::iter::once(&path))
+        };
+
+        let report = check("[producers]\n[consumers]\n");
+        assert!(report.is_ok());
+        assert_eq!(report.unknown_sections, vec!["consumers"]);
+
+        let report =
+            check("[producers]\n[consumers]\n[custom.configurers]\ndeny_unknown_sections = true\n");
+        assert!(!report.is_ok());
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].reason, "unknown section `consumers`");
+
+        let report = check("[producers]\n[custom.configurers]\ndeny_unknown_sections = 42\n");
+        assert!(!report.is_ok());
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(
+            report.errors[0].group.as_deref(),
+            Some("custom.configurers")
+        );
+
+        let _ = std::fs::remove_file(&path);
+    }
+}
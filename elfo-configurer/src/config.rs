@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+
+/// The configurer's own config, it's taken from the configurer's section,
+/// e.g. `[system.configurers]`.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct Config {
+    /// If set, config files are watched and reloaded after changes.
+    pub(crate) watch: Option<WatchConfig>,
+    /// Reject configs with sections that match no actor group.
+    #[serde(default)]
+    pub(crate) deny_unknown_sections: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct WatchConfig {
+    /// How often files are checked for changes (by mtime and size).
+    #[serde(with = "humantime_serde", default = "default_interval")]
+    pub(crate) interval: Duration,
+    /// How long files must stay unchanged before reloading.
+    /// Editors often write files in several steps.
+    #[serde(with = "humantime_serde", default = "default_debounce")]
+    pub(crate) debounce: Duration,
+}
+
+fn default_interval() -> Duration {
+    Duration::from_secs(1)
+}
+
+fn default_debounce() -> Duration {
+    Duration::from_millis(500)
+}
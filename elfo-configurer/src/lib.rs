@@ -1,7 +1,10 @@
 #![warn(rust_2018_idioms, unreachable_pub)]
 
+#[macro_use]
+extern crate elfo_utils;
+
 use std::{
-    fs,
+    fmt,
     path::{Path, PathBuf},
 };
 
@@ -9,7 +12,7 @@ use futures::{future, FutureExt};
 use fxhash::FxHashMap;
 use serde::{de::Deserializer, Deserialize};
 use serde_value::Value;
-use tracing::error;
+use tracing::{error, info, warn};
 
 use elfo_core as elfo;
 use elfo_macros::{message, msg_raw as msg};
@@ -19,26 +22,95 @@ use elfo::{
     errors::RequestError,
     messages::{ConfigRejected, Ping, UpdateConfig, ValidateConfig},
     signal::{Signal, SignalKind},
+    time::{Interval, Stopwatch},
     ActorGroup, ActorStatus, Addr, Context, Request, Schema, Topology,
 };
 
+pub use crate::check::{check_config, check_config_by_args, CheckConfigReport};
+
+use crate::{
+    config::Config,
+    source::{ConfigSource, Fingerprint},
+};
+
+mod check;
+mod config;
+mod source;
+
 pub fn fixture(topology: &Topology, config: impl for<'de> Deserializer<'de>) -> Schema {
     let config = Value::deserialize(config).map_err(|err| err.to_string());
     let source = ConfigSource::Fixture(config);
-    let topology = topology.clone();
-    ActorGroup::new().exec(move |ctx| Configurer::new(ctx, topology.clone(), source.clone()).main())
+    configurer(topology, source)
 }
 
 pub fn from_path(topology: &Topology, path_to_config: impl AsRef<Path>) -> Schema {
-    let source = ConfigSource::File(path_to_config.as_ref().to_path_buf());
+    from_paths(topology, std::iter::once(path_to_config))
+}
+
+/// Loads configs from multiple files (layers) and merges them by keys,
+/// later files override earlier ones. Thus, it's possible to have a base file
+/// and environment-specific files containing only changed parts.
+///
+/// Every file can include other files by the top-level `include` key
+/// (a path or a list of paths relative to the file), included files are
+/// loaded before the including one.
+///
+/// Also, any field can be overridden by environment variables in the form
+/// `ELFO__<section>__<subsection>__<field>=<value>`, e.g.
+/// `ELFO__system__loggers__sink=File`. Values are parsed as TOML values
+/// and fall back to strings.
+///
+/// All layers are reread on reloading.
+///
+/// The configurer registers the config loader in the topology, so entrypoints
+/// get their configs from the same files at startup, before the configurer
+/// itself is started.
+///
+/// Files can be watched and reloaded automatically after changes.
+/// It's enabled in the configurer's section, e.g.
+/// ```toml
+/// [system.configurers]
+/// watch = { interval = "1s", debounce = "500ms" }
+/// ```
+pub fn from_paths<P: AsRef<Path>>(
+    topology: &Topology,
+    paths_to_configs: impl IntoIterator<Item = P>,
+) -> Schema {
+    let paths = paths_to_configs
+        .into_iter()
+        .map(|path| path.as_ref().to_path_buf())
+        .collect();
+    let source = ConfigSource::Files(paths);
+    configurer(topology, source)
+}
+
+fn configurer(topology: &Topology, source: ConfigSource) -> Schema {
+    // Entrypoints are started before the configurer, so the starter loads
+    // their configs on its own.
+    let loader_source = source.clone();
+    topology.set_config_loader(move |topology| entrypoint_configs(topology, &loader_source));
+
     let topology = topology.clone();
-    ActorGroup::new().exec(move |ctx| Configurer::new(ctx, topology.clone(), source.clone()).main())
+    ActorGroup::new()
+        .config::<Config>()
+        .exec(move |ctx| Configurer::new(ctx, topology.clone(), source.clone()).main())
 }
 
-#[derive(Clone)]
-enum ConfigSource {
-    File(PathBuf),
-    Fixture(Result<Value, String>),
+fn entrypoint_configs(
+    topology: &Topology,
+    source: &ConfigSource,
+) -> Result<Vec<(String, AnyConfig)>, String> {
+    let config = source.load(&mut Vec::new())?;
+    let configs: FxHashMap<String, Value> = Deserialize::deserialize(config).unwrap_or_default();
+
+    Ok(topology
+        .actor_groups()
+        .filter(|group| group.is_entrypoint)
+        .filter_map(|group| {
+            let config = get_config(&configs, &group.name)?;
+            Some((group.name, AnyConfig::new(config)))
+        })
+        .collect())
 }
 
 #[derive(Clone)]
@@ -66,77 +138,326 @@ impl ReloadConfigs {
     }
 }
 
+/// The same as `ReloadConfigs`, but returns the outcome of reloading.
+#[non_exhaustive]
+#[message(ret = Result<(), TryReloadConfigsRejected>, elfo = elfo_core)]
+#[derive(Default)]
+pub struct TryReloadConfigs {
+    force: bool,
+}
+
+impl TryReloadConfigs {
+    /// If enabled, all configs will be updated, including up-to-date ones.
+    pub fn with_force(self, force: bool) -> Self {
+        TryReloadConfigs { force }
+    }
+}
+
+/// Describes why reloading has failed.
+///
+/// System groups are updated before user ones. If some groups fail to
+/// validate or update, all groups updated during the reload (both system and
+/// user ones) are rolled back to their last good configs. Groups that cannot be
+/// rolled back (e.g. never configured before) are reported as inconsistent.
+#[non_exhaustive]
+#[message(part, elfo = elfo_core)]
+pub struct TryReloadConfigsRejected {
+    pub errors: Vec<ReloadConfigsError>,
+    /// Groups rolled back to their last good configs.
+    pub rolled_back: Vec<String>,
+    /// Groups that can have inconsistent configs.
+    pub inconsistent: Vec<String>,
+}
+
+#[non_exhaustive]
+#[message(part, elfo = elfo_core)]
+pub struct ReloadConfigsError {
+    /// `None` if the error isn't related to some group, e.g. a syntax error.
+    pub group: Option<String>,
+    pub reason: String,
+}
+
+impl fmt::Display for ReloadConfigsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.group {
+            Some(group) => write!(f, "{}: {}", group, self.reason),
+            None => f.write_str(&self.reason),
+        }
+    }
+}
+
+impl TryReloadConfigsRejected {
+    fn new(errors: Vec<ReloadConfigsError>) -> Self {
+        Self {
+            errors,
+            rolled_back: Vec::new(),
+            inconsistent: Vec::new(),
+        }
+    }
+}
+
+/// Returns configs that groups currently run with, after layering and reloads.
+/// `Secret` fields are redacted.
+#[non_exhaustive]
+#[message(ret = Vec<EffectiveConfig>, elfo = elfo_core)]
+pub struct GetEffectiveConfig {
+    group: Option<String>,
+}
+
+impl GetEffectiveConfig {
+    /// Requests the config of the specified group.
+    pub fn group(name: impl Into<String>) -> Self {
+        Self {
+            group: Some(name.into()),
+        }
+    }
+
+    /// Requests configs of all groups.
+    pub fn all() -> Self {
+        Self { group: None }
+    }
+}
+
+#[non_exhaustive]
+#[message(part, elfo = elfo_core)]
+pub struct EffectiveConfig {
+    pub group: String,
+    pub config: Value,
+}
+
+#[message(elfo = elfo_core)]
+struct CheckConfigFiles;
+
+#[message(elfo = elfo_core)]
+struct ConfigFilesChanged;
+
 struct Configurer {
-    ctx: Context,
+    ctx: Context<Config>,
     topology: Topology,
     source: ConfigSource,
-    /// Stores hashes of configs per group.
-    versions: FxHashMap<String, u64>,
+    /// Stores the last successfully applied config per group.
+    last_good: FxHashMap<String, ConfigWithMeta>,
+    /// The configurer's own config.
+    config: Config,
+    /// Files touched during the last loading.
+    files: Vec<PathBuf>,
+    fingerprint: Fingerprint,
 }
 
 impl Configurer {
-    fn new(ctx: Context, topology: Topology, source: ConfigSource) -> Self {
+    fn new(ctx: Context<Config>, topology: Topology, source: ConfigSource) -> Self {
         Self {
             ctx,
             topology,
             source,
-            versions: FxHashMap::default(),
+            last_good: FxHashMap::default(),
+            config: Config::default(),
+            files: Vec::new(),
+            fingerprint: Fingerprint::new(),
         }
     }
 
     async fn main(mut self) {
         let signal = Signal::new(SignalKind::Hangup, ReloadConfigs::default);
-        let mut ctx = self.ctx.clone().with(&signal);
-        let can_start = self.load_and_update_configs(true).await;
+        let watcher = Interval::new(|| CheckConfigFiles);
+        let debouncer = Stopwatch::new(|| ConfigFilesChanged);
+        let mut ctx = self
+            .ctx
+            .clone()
+            .with(&signal)
+            .with(&watcher)
+            .with(&debouncer);
+
+        // Entrypoints have already got their configs from the starter, so
+        // actors with unchanged configs shouldn't be updated once again.
+        let can_start = self.load_and_update_configs(false).await.is_ok();
+        self.update_watcher(&watcher);
 
         while let Some(envelope) = ctx.recv().await {
             msg!(match envelope {
                 (Ping, token) if can_start => ctx.respond(token, ()),
                 (Ping, token) => drop(token),
                 ReloadConfigs { force } => {
-                    self.load_and_update_configs(force).await;
+                    let _ = self.load_and_update_configs(force).await;
+                    self.update_watcher(&watcher);
+                }
+                (TryReloadConfigs { force }, token) => {
+                    let result = self.load_and_update_configs(force).await;
+                    self.update_watcher(&watcher);
+                    ctx.respond(token, result);
+                }
+                (GetEffectiveConfig { group }, token) => {
+                    let configs = self.effective_configs(group.as_deref());
+                    ctx.respond(token, configs);
+                }
+                CheckConfigFiles => {
+                    let debounce = ward!(&self.config.watch, continue).debounce;
+
+                    if self.detect_changes() {
+                        debouncer.schedule_after(debounce);
+                    }
+                }
+                ConfigFilesChanged => {
+                    let debounce = ward!(&self.config.watch, continue).debounce;
+
+                    // Postpone reloading while files are changing, regardless
+                    // of how often they're checked by the watcher.
+                    if self.detect_changes() {
+                        debouncer.schedule_after(debounce);
+                        continue;
+                    }
+
+                    info!("config files changed, reloading");
+                    let _ = self.load_and_update_configs(false).await;
+                    self.update_watcher(&watcher);
                 }
             })
         }
     }
 
-    async fn load_and_update_configs(&mut self, force: bool) -> bool {
-        let config = match &self.source {
-            ConfigSource::File(path) => load_raw_config(path),
-            ConfigSource::Fixture(value) => value.clone(),
-        };
+    fn effective_configs(&self, group_name: Option<&str>) -> Vec<EffectiveConfig> {
+        self.topology
+            .actor_groups()
+            .filter(|group| group_name.is_none() || group_name == Some(&group.name[..]))
+            .filter_map(|group| {
+                let last_good = self.last_good.get(&group.name)?;
+                let config = serde_value::to_value(&last_good.config).ok()?;
+                Some(EffectiveConfig {
+                    config: group.redact_config(config),
+                    group: group.name,
+                })
+            })
+            .collect()
+    }
 
-        let config = match config {
+    fn update_watcher<F>(&self, watcher: &Interval<F>) {
+        match &self.config.watch {
+            Some(watch) => watcher.set_period(watch.interval),
+            None => watcher.stop(),
+        }
+    }
+
+    /// Returns `true` if files have changed since the last check.
+    fn detect_changes(&mut self) -> bool {
+        let fingerprint = source::fingerprint(&self.files);
+        let changed = fingerprint != self.fingerprint;
+        self.fingerprint = fingerprint;
+        changed
+    }
+
+    async fn load_and_update_configs(
+        &mut self,
+        force: bool,
+    ) -> Result<(), TryReloadConfigsRejected> {
+        let mut files = Vec::new();
+        let result = self.source.load(&mut files);
+
+        // Track files even if loading fails in order to reload after fixing.
+        self.fingerprint = source::fingerprint(&files);
+        self.files = files;
+
+        let config = match result {
             Ok(config) => config,
             Err(reason) => {
                 error!(%reason, "invalid config");
-                return false;
+                let error = ReloadConfigsError {
+                    group: None,
+                    reason,
+                };
+                return Err(TryReloadConfigsRejected::new(vec![error]));
             }
         };
 
-        let system_updated = self
-            .update_configs(&config, TopologyFilter::System, force)
-            .await;
-        let user_udpated = self
-            .update_configs(&config, TopologyFilter::User, force)
-            .await;
-        system_updated && user_udpated
+        match self.decode_own_config(&config) {
+            Ok(own_config) => self.config = own_config,
+            Err(error) => {
+                error!(reason = %error.reason, "invalid configurer's config");
+                return Err(TryReloadConfigsRejected::new(vec![error]));
+            }
+        }
+
+        let unknown_sections = find_unknown_sections(&self.topology, &config);
+        for section in &unknown_sections {
+            warn!(%section, "config section matches no actor group");
+        }
+
+        if self.config.deny_unknown_sections && !unknown_sections.is_empty() {
+            error!("config contains unknown sections");
+            let errors = unknown_sections
+                .into_iter()
+                .map(|section| ReloadConfigsError {
+                    group: None,
+                    reason: format!("unknown section `{}`", section),
+                })
+                .collect();
+            return Err(TryReloadConfigsRejected::new(errors));
+        }
+
+        // Groups updated during this reload, they're rolled back together
+        // if any phase fails, so system groups don't keep new configs
+        // rejected by user groups and vice versa.
+        let mut updated = Vec::new();
+
+        for filter in [TopologyFilter::System, TopologyFilter::User] {
+            if let Err(errors) = self
+                .update_configs(&config, filter, force, &mut updated)
+                .await
+            {
+                return Err(self.rollback(updated, errors).await);
+            }
+        }
+
+        self.ctx.set_status(ActorStatus::NORMAL);
+
+        // Update last good configs.
+        self.last_good
+            .extend(updated.into_iter().map(|c| (c.group_name.clone(), c)));
+
+        Ok(())
+    }
+
+    fn decode_own_config(&self, config: &Value) -> Result<Config, ReloadConfigsError> {
+        let configs: FxHashMap<String, Value> =
+            Deserialize::deserialize(config.clone()).unwrap_or_default();
+
+        let own_group = self
+            .topology
+            .actor_groups()
+            .find(|group| group.addr == self.ctx.group())
+            .map(|group| group.name);
+
+        own_group
+            .as_ref()
+            .and_then(|name| get_config(&configs, name))
+            .map_or_else(|| Ok(Config::default()), Config::deserialize)
+            .map_err(|err| ReloadConfigsError {
+                group: own_group.clone(),
+                reason: err.to_string(),
+            })
     }
 
+    /// Validates and updates configs of groups matched by the filter.
+    /// Groups that have been requested to update are pushed to `updated`,
+    /// even if some of them reject the new config.
     async fn update_configs(
         &mut self,
         config: &Value,
         filter: TopologyFilter,
         force: bool,
-    ) -> bool {
+        updated: &mut Vec<ConfigWithMeta>,
+    ) -> Result<(), Vec<ReloadConfigsError>> {
         let mut config_list = match_configs(&self.topology, config.clone(), filter);
 
+        // The configurer's own config is decoded separately, requesting itself
+        // would lead to a deadlock.
+        config_list.retain(|c| c.addr != self.ctx.group());
+
         // Filter up-to-date configs if needed.
         if !force {
             config_list.retain(|c| {
-                self.versions
+                self.last_good
                     .get(&c.group_name)
-                    .map_or(true, |v| c.hash != *v)
+                    .map_or(true, |last| c.hash != last.hash)
             });
         }
 
@@ -144,27 +465,30 @@ impl Configurer {
         let status = ActorStatus::NORMAL.with_details("config validation");
         self.ctx.set_status(status);
 
-        if !self
+        let errors = self
             .request_all(&config_list, |config| ValidateConfig { config })
-            .await
-        {
+            .await;
+
+        if !errors.is_empty() {
             error!("config validation failed");
-            self.ctx.set_status(ActorStatus::NORMAL);
-            return false;
+            return Err(errors);
         }
 
         // Updating.
         let status = ActorStatus::NORMAL.with_details("config updating");
         self.ctx.set_status(status);
 
-        if !self
-            .request_all(&config_list, |config| UpdateConfig { config })
-            .await
-        {
+        let errors = self
+            .request_all(&config_list, |config| {
+                UpdateConfig::new(config).with_force(force)
+            })
+            .await;
+
+        updated.extend(config_list);
+
+        if !errors.is_empty() {
             error!("config updating failed");
-            self.ctx
-                .set_status(ActorStatus::ALARMING.with_details("possibly incosistent configs"));
-            return false;
+            return Err(errors);
         }
 
         // TODO: make `Ping` automatically handled.
@@ -174,20 +498,68 @@ impl Configurer {
         // configs")); return false;
         // }
 
-        self.ctx.set_status(ActorStatus::NORMAL);
+        Ok(())
+    }
+
+    /// Sends the last good configs to all groups touched by the failed reload.
+    async fn rollback(
+        &mut self,
+        config_list: Vec<ConfigWithMeta>,
+        errors: Vec<ReloadConfigsError>,
+    ) -> TryReloadConfigsRejected {
+        let mut rejected = TryReloadConfigsRejected::new(errors);
+
+        // Nothing has been updated, e.g. validation of system groups failed.
+        if config_list.is_empty() {
+            self.ctx.set_status(ActorStatus::NORMAL);
+            return rejected;
+        }
+
+        let status = ActorStatus::NORMAL.with_details("config rollback");
+        self.ctx.set_status(status);
 
-        // Update versions.
-        self.versions
-            .extend(config_list.into_iter().map(|c| (c.group_name, c.hash)));
+        let mut rollback_list = Vec::with_capacity(config_list.len());
 
-        true
+        for item in config_list {
+            match self.last_good.get(&item.group_name) {
+                Some(last_good) => rollback_list.push(last_good.clone()),
+                None => rejected.inconsistent.push(item.group_name),
+            }
+        }
+
+        let errors = self.request_all(&rollback_list, UpdateConfig::new).await;
+
+        for item in rollback_list {
+            let group = Some(&item.group_name);
+            if errors.iter().any(|error| error.group.as_ref() == group) {
+                rejected.inconsistent.push(item.group_name);
+            } else {
+                rejected.rolled_back.push(item.group_name);
+            }
+        }
+
+        if rejected.inconsistent.is_empty() {
+            info!(rolled_back = ?rejected.rolled_back, "configs rolled back");
+            self.ctx.set_status(ActorStatus::NORMAL);
+        } else {
+            error!(
+                rolled_back = ?rejected.rolled_back,
+                inconsistent = ?rejected.inconsistent,
+                "configs rollback failed"
+            );
+            self.ctx
+                .set_status(ActorStatus::ALARMING.with_details("possibly incosistent configs"));
+        }
+
+        rejected.errors.extend(errors);
+        rejected
     }
 
     async fn request_all<R>(
         &self,
         config_list: &[ConfigWithMeta],
         make_msg: impl Fn(AnyConfig) -> R,
-    ) -> bool
+    ) -> Vec<ReloadConfigsError>
     where
         R: Request<Response = Result<(), ConfigRejected>>,
     {
@@ -206,7 +578,7 @@ impl Configurer {
             .collect::<Vec<_>>();
 
         // TODO: use `try_join_all`.
-        let errors = future::join_all(futures)
+        future::join_all(futures)
             .await
             .into_iter()
             .map(|(group, results)| results.into_iter().map(move |res| (group.clone(), res)))
@@ -214,13 +586,16 @@ impl Configurer {
             .filter_map(|(group, result)| match result {
                 Ok(Ok(_)) | Err(_) => None,
                 Ok(Err(reject)) => Some((group, reject.reason)),
-                /* TODO: it's meaningful, but doesn't work well with empty groups.
-                 * Err(RequestError::Closed(_)) => Some((group, "some group is closed".into())), */
+                // TODO: it's meaningful, but doesn't work well with empty groups.
+                // Err(RequestError::Closed(_)) => Some((group, "some group is closed".into())),
             })
             // TODO: provide more info.
-            .inspect(|(group, reason)| error!(%group, %reason, "invalid config"));
-
-        errors.count() == 0
+            .inspect(|(group, reason)| error!(%group, %reason, "invalid config"))
+            .map(|(group, reason)| ReloadConfigsError {
+                group: Some(group),
+                reason,
+            })
+            .collect()
     }
 }
 
@@ -252,11 +627,6 @@ async fn ping(ctx: &Context, config_list: &[ConfigWithMeta]) -> bool {
     errors.count() == 0
 }
 
-fn load_raw_config(path: impl AsRef<Path>) -> Result<Value, String> {
-    let content = fs::read_to_string(path).map_err(|err| err.to_string())?;
-    toml::from_str(&content).map_err(|err| err.to_string())
-}
-
 fn match_configs(
     topology: &Topology,
     config: Value,
@@ -266,8 +636,6 @@ fn match_configs(
 
     topology
         .actor_groups()
-        // Entrypoints' configs are updated only at startup.
-        .filter(|group| !group.is_entrypoint)
         .filter(|group| match filter {
             TopologyFilter::System => group.name.starts_with("system."),
             TopologyFilter::User => !group.name.starts_with("system."),
@@ -285,6 +653,54 @@ fn match_configs(
         .collect()
 }
 
+/// Returns dotted paths of sections that are neither groups' sections
+/// nor their parents, e.g. `sytem` (for `[sytem.loggers]`) or `system.unknown`.
+/// Only the outermost unknown section is reported.
+fn find_unknown_sections(topology: &Topology, config: &Value) -> Vec<String> {
+    let groups = topology
+        .actor_groups()
+        .map(|group| group.name)
+        .collect::<Vec<_>>();
+
+    let mut unknown = Vec::new();
+    collect_unknown_sections(&groups, config, "", &mut unknown);
+    unknown
+}
+
+fn collect_unknown_sections(
+    groups: &[String],
+    node: &Value,
+    prefix: &str,
+    unknown: &mut Vec<String>,
+) {
+    let map = match node {
+        Value::Map(map) => map,
+        _ => return,
+    };
+
+    for (key, value) in map {
+        let path = match key {
+            Value::String(key) if prefix.is_empty() => key.clone(),
+            Value::String(key) => format!("{}.{}", prefix, key),
+            _ => continue,
+        };
+
+        if groups.contains(&path) {
+            continue;
+        }
+
+        let is_parent = groups
+            .iter()
+            .any(|group| group.starts_with(&path) && group[path.len()..].starts_with('.'));
+
+        if is_parent && matches!(value, Value::Map(_)) {
+            collect_unknown_sections(groups, value, &path, unknown);
+        } else {
+            unknown.push(path);
+        }
+    }
+}
+
 fn get_config(configs: &FxHashMap<String, Value>, path: &str) -> Option<Value> {
     let mut parts_iter = path.split('.');
     let mut node = configs.get(parts_iter.next()?)?;
@@ -334,6 +750,35 @@ mod test {
         );
     }
 
+    #[test]
+    fn find_unknown_sections_should_ignore_groups_and_parents() {
+        let topology = Topology::empty();
+        for name in &["producers", "system.loggers", "system.configurers"] {
+            let _ = topology.local(*name);
+        }
+
+        let config = toml::from_str(
+            r#"
+                [producers]
+                count = 1
+                [consumers]
+                count = 2
+                [system.loggers]
+                sink = "File"
+                [system.loger]
+                sink = "File"
+                [sytem.loggers]
+                sink = "File"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            find_unknown_sections(&topology, &config),
+            vec!["consumers", "system.loger", "sytem"]
+        );
+    }
+
     /// ```json
     /// {
     ///     "alpha": "beta",
@@ -0,0 +1,226 @@
+use std::{
+    env, fmt, fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde_value::Value;
+
+use elfo_core::_priv::merge;
+
+/// The prefix of environment variables overriding configs.
+/// E.g. `ELFO__system__loggers__sink=File` sets the `sink` field
+/// of the `[system.loggers]` section.
+const ENV_PREFIX: &str = "ELFO__";
+const ENV_SEPARATOR: &str = "__";
+
+/// The top-level key of a file containing paths to other files that are
+/// loaded before the file itself. Paths are relative to the including file.
+const INCLUDE_KEY: &str = "include";
+
+#[derive(Clone)]
+pub(crate) enum ConfigSource {
+    /// Layers, later files override earlier ones.
+    Files(Vec<PathBuf>),
+    Fixture(Result<Value, String>),
+}
+
+impl ConfigSource {
+    /// Loads the whole config. All touched files, including included ones
+    /// and ones failed to be read, are pushed to `files`.
+    pub(crate) fn load(&self, files: &mut Vec<PathBuf>) -> Result<Value, String> {
+        match self {
+            ConfigSource::Files(paths) => {
+                let mut config = Value::Map(Default::default());
+                for path in paths {
+                    merge(&mut config, load_file(path, &mut Vec::new(), files)?);
+                }
+                // Variables with non-UTF-8 names or values can't be overrides.
+                let vars = env::vars_os().filter_map(|(name, value)| {
+                    Some((name.into_string().ok()?, value.into_string().ok()?))
+                });
+                apply_env_overrides(&mut config, vars);
+                Ok(config)
+            }
+            ConfigSource::Fixture(value) => value.clone(),
+        }
+    }
+}
+
+fn load_file(
+    path: &Path,
+    stack: &mut Vec<PathBuf>,
+    files: &mut Vec<PathBuf>,
+) -> Result<Value, String> {
+    if !files.iter().any(|file| file == path) {
+        files.push(path.to_path_buf());
+    }
+
+    let canonical = fs::canonicalize(path).map_err(|err| with_path(path, err))?;
+    if stack.contains(&canonical) {
+        return Err(with_path(path, "include cycle detected"));
+    }
+
+    let content = fs::read_to_string(path).map_err(|err| with_path(path, err))?;
+    let mut value: Value = toml::from_str(&content).map_err(|err| with_path(path, err))?;
+
+    let includes = match &mut value {
+        Value::Map(map) => map.remove(&Value::String(INCLUDE_KEY.into())),
+        _ => None,
+    };
+
+    let includes = match includes {
+        None => return Ok(value),
+        Some(Value::String(include)) => vec![include],
+        Some(Value::Seq(includes)) => includes
+            .into_iter()
+            .map(|include| match include {
+                Value::String(include) => Ok(include),
+                _ => Err(with_path(path, "`include` must contain only strings")),
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => {
+            return Err(with_path(
+                path,
+                "`include` must be a string or a list of strings",
+            ))
+        }
+    };
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut config = Value::Map(Default::default());
+
+    stack.push(canonical);
+    for include in includes {
+        merge(&mut config, load_file(&dir.join(include), stack, files)?);
+    }
+    stack.pop();
+
+    merge(&mut config, value);
+    Ok(config)
+}
+
+/// The state of files used to detect changes.
+pub(crate) type Fingerprint = Vec<Option<(SystemTime, u64)>>;
+
+/// Collects mtimes and sizes of files, missing files are also tracked.
+pub(crate) fn fingerprint(files: &[PathBuf]) -> Fingerprint {
+    files
+        .iter()
+        .map(|path| {
+            let meta = fs::metadata(path).ok()?;
+            Some((meta.modified().ok()?, meta.len()))
+        })
+        .collect()
+}
+
+fn with_path(path: &Path, err: impl fmt::Display) -> String {
+    format!("{}: {}", path.display(), err)
+}
+
+fn apply_env_overrides(config: &mut Value, vars: impl Iterator<Item = (String, String)>) {
+    let mut vars = vars
+        .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+        .collect::<Vec<_>>();
+
+    // Apply in the predictable order, so `ELFO__a__b` always overrides `ELFO__a`.
+    vars.sort();
+
+    for (name, raw) in vars {
+        let path = name[ENV_PREFIX.len()..].split(ENV_SEPARATOR);
+        set_by_path(config, path, parse_env_value(&raw));
+    }
+}
+
+fn set_by_path<'a>(node: &mut Value, mut path: impl Iterator<Item = &'a str>, value: Value) {
+    let part = match path.next() {
+        Some(part) => part,
+        None => return *node = value,
+    };
+
+    if !matches!(node, Value::Map(_)) {
+        *node = Value::Map(Default::default());
+    }
+
+    if let Value::Map(map) = node {
+        let child = map.entry(Value::String(part.into())).or_insert(Value::Unit);
+        set_by_path(child, path, value);
+    }
+}
+
+/// Parses a value as TOML (numbers, booleans, arrays and so on).
+/// Falls back to a raw string, so quotes can be omitted.
+fn parse_env_value(raw: &str) -> Value {
+    let parsed = toml::from_str::<Value>(&format!("value = {}", raw))
+        .ok()
+        .and_then(|value| match value {
+            Value::Map(mut map) => map.remove(&Value::String("value".into())),
+            _ => None,
+        });
+
+    match parsed {
+        // TOML datetimes are represented as maps, keep them as strings.
+        Some(Value::Map(_)) if !raw.trim_start().starts_with('{') => Value::String(raw.into()),
+        Some(value) => value,
+        None => Value::String(raw.into()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn toml(s: &str) -> Value {
+        toml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn env_overrides_should_be_applied() {
+        let mut config = toml(
+            r#"
+                [system.loggers]
+                sink = "Stdout"
+                [producers]
+                item_count = 10
+            "#,
+        );
+
+        let vars = vec![
+            ("HOME", "/root"),
+            ("ELFO__producers__item_count", "20"),
+            ("ELFO__producers__tags", "[\"a\", \"b\"]"),
+            ("ELFO__system__loggers__sink", "File"),
+            ("ELFO__system__loggers__path", "\"elfo.log\""),
+            ("ELFO__reporters__interval", "2s"),
+        ];
+
+        let vars = vars.into_iter().map(|(k, v)| (k.into(), v.into()));
+        apply_env_overrides(&mut config, vars);
+
+        let expected = toml(
+            r#"
+                [system.loggers]
+                sink = "File"
+                path = "elfo.log"
+                [producers]
+                item_count = 20
+                tags = ["a", "b"]
+                [reporters]
+                interval = "2s"
+            "#,
+        );
+
+        assert_eq!(config, expected);
+    }
+
+    #[test]
+    fn env_value_should_fallback_to_string() {
+        assert_eq!(parse_env_value("42"), Value::I64(42));
+        assert_eq!(parse_env_value("true"), Value::Bool(true));
+        assert_eq!(parse_env_value("some"), Value::String("some".into()));
+        assert_eq!(
+            parse_env_value("1979-05-27"),
+            Value::String("1979-05-27".into())
+        );
+    }
+}
@@ -0,0 +1,113 @@
+//! Tracks whether actors wait for new messages.
+//! Used by `elfo-test` to wait until the actor under test becomes idle.
+
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use dashmap::DashMap;
+use fxhash::FxBuildHasher;
+use once_cell::sync::Lazy;
+use tokio::time::Instant;
+
+use crate::{addr::Addr, address_book::AddressBook, context::Context, topology::Topology};
+
+/// (Book's id, actor's address).
+type ActorKey = (usize, Addr);
+type Actors = DashMap<ActorKey, ActorActivity, FxBuildHasher>;
+
+static ACTORS: Lazy<Actors> = Lazy::new(Default::default);
+
+tokio::task_local! {
+    static BOOK_ID: usize;
+}
+
+struct ActorActivity {
+    group: Addr,
+    restart_at: Option<Instant>,
+}
+
+/// Registers the actor until the returned guard is dropped.
+pub(crate) fn track(book: &AddressBook, group: Addr, addr: Addr) -> Tracked {
+    let key = (book.id(), addr);
+    let activity = ActorActivity {
+        group,
+        restart_at: None,
+    };
+
+    ACTORS.insert(key, activity);
+    Tracked { key }
+}
+
+/// Runs the actor's task in the scope of its address book, see
+/// `current_book_id()`.
+pub(crate) fn scope<F: Future>(book: &AddressBook, fut: F) -> impl Future<Output = F::Output> {
+    BOOK_ID.scope(book.id(), fut)
+}
+
+/// Distinguishes topologies, e.g. in order to route events and metrics
+/// of actors to the right test proxy.
+pub fn book_id(topology: &Topology) -> usize {
+    topology.book.id()
+}
+
+/// Returns the id of the topology the current actor belongs to. Tasks spawned
+/// by actors aren't inside the topology.
+pub fn current_book_id() -> Option<usize> {
+    BOOK_ID.try_with(|book_id| *book_id).ok()
+}
+
+/// Returns `true` if all actors of the group are idle, i.e. wait for new
+/// messages in `Context::recv()` and have empty mailboxes. Failed actors are
+/// idle until the time to restart them, because only a timer can wake them.
+/// Tasks spawned by actors aren't taken into account.
+pub fn is_group_idle<C, K, S>(ctx: &Context<C, K, S>, group: Addr) -> bool {
+    let book = ctx.book();
+    let now = Instant::now();
+
+    ACTORS
+        .iter()
+        .filter(|entry| entry.key().0 == book.id() && entry.group == group)
+        .all(|entry| match entry.restart_at {
+            Some(restart_at) => now < restart_at,
+            None => book
+                .get(entry.key().1)
+                .and_then(|object| object.as_actor().map(|actor| actor.is_idle()))
+                .unwrap_or(true),
+        })
+}
+
+pub(crate) struct Tracked {
+    key: ActorKey,
+}
+
+impl Tracked {
+    /// Marks the failed actor waiting for a restart.
+    pub(crate) fn restart_at(&self, restart_at: Instant) {
+        if let Some(mut activity) = ACTORS.get_mut(&self.key) {
+            activity.restart_at = Some(restart_at);
+        }
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        ACTORS.remove(&self.key);
+    }
+}
+
+pub(crate) struct Parked<'a>(&'a AtomicBool);
+
+impl<'a> Parked<'a> {
+    pub(crate) fn new(parked: &'a AtomicBool) -> Self {
+        parked.store(true, Ordering::SeqCst);
+        Self(parked)
+    }
+}
+
+impl Drop for Parked<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::SeqCst);
+    }
+}
@@ -1,4 +1,6 @@
-use std::fmt;
+#[cfg(feature = "test-util")]
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{fmt, sync::Arc};
 
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
@@ -9,13 +11,52 @@ use crate::{
     envelope::Envelope,
     errors::{SendError, TryRecvError, TrySendError},
     mailbox::Mailbox,
+    object::ObjectMeta,
     request_table::RequestTable,
 };
 
+type StatusHook = dyn Fn(&Arc<ObjectMeta>, &ActorStatus) + Send + Sync;
+
+/// Hooks called on every change of actors' statuses, registered by groups.
+/// Every topology has its own hooks, so they're dropped with the topology.
+#[derive(Clone, Default)]
+pub(crate) struct StatusHooks(Arc<RwLock<Vec<Arc<StatusHook>>>>);
+
+impl StatusHooks {
+    pub(crate) fn add(
+        &self,
+        hook: impl Fn(&Arc<ObjectMeta>, &ActorStatus) + Send + Sync + 'static,
+    ) {
+        self.0.write().push(Arc::new(hook));
+    }
+
+    pub(crate) fn extend(&self, other: &StatusHooks) {
+        let other = other.0.read().clone();
+        self.0.write().extend(other);
+    }
+
+    fn call(&self, meta: &Arc<ObjectMeta>, status: &ActorStatus) {
+        for hook in self.0.read().iter() {
+            hook(meta, status);
+        }
+    }
+}
+
+impl fmt::Debug for StatusHooks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "StatusHooks({})", self.0.read().len())
+    }
+}
+
 pub(crate) struct Actor {
+    meta: Arc<ObjectMeta>,
     mailbox: Mailbox,
     request_table: RequestTable,
     control: RwLock<ControlBlock>,
+    status_hooks: StatusHooks,
+    /// Whether the actor waits for new messages in `Context::recv()`.
+    #[cfg(feature = "test-util")]
+    parked: AtomicBool,
 }
 
 struct ControlBlock {
@@ -67,19 +108,27 @@ impl ActorStatus {
         }
     }
 
-    pub(crate) fn is_failed(&self) -> bool {
+    pub fn is_failed(&self) -> bool {
         self.kind == ActorStatusKind::Failed
     }
+
+    pub fn is_terminated(&self) -> bool {
+        self.kind == ActorStatusKind::Terminated
+    }
 }
 
 impl Actor {
-    pub(crate) fn new(addr: Addr) -> Self {
+    pub(crate) fn new(addr: Addr, meta: Arc<ObjectMeta>, status_hooks: StatusHooks) -> Self {
         Actor {
+            meta,
             mailbox: Mailbox::new(),
             request_table: RequestTable::new(addr),
             control: RwLock::new(ControlBlock {
                 status: ActorStatus::INITIALIZING,
             }),
+            status_hooks,
+            #[cfg(feature = "test-util")]
+            parked: AtomicBool::new(false),
         }
     }
 
@@ -113,11 +162,26 @@ impl Actor {
         self.mailbox.try_recv()
     }
 
+    /// Marks the actor parked until the returned guard is dropped.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn park(&self) -> crate::activity::Parked<'_> {
+        crate::activity::Parked::new(&self.parked)
+    }
+
+    /// Returns `true` if the actor is parked and has no messages to handle.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn is_idle(&self) -> bool {
+        self.parked.load(Ordering::SeqCst) && self.mailbox.is_empty()
+    }
+
     pub(crate) fn request_table(&self) -> &RequestTable {
         &self.request_table
     }
 
     pub(crate) fn set_status(&self, status: ActorStatus) {
+        // Hooks are called before the change is logged.
+        self.status_hooks.call(&self.meta, &status);
+
         let mut control = self.control.write();
 
         let is_good_kind = matches!(
@@ -21,6 +21,12 @@ impl AddressBook {
         }
     }
 
+    /// Distinguishes address books of different topologies.
+    #[cfg(feature = "test-util")]
+    pub(crate) fn id(&self) -> usize {
+        Arc::as_ptr(&self.slab) as usize
+    }
+
     pub(crate) fn get(&self, addr: Addr) -> Option<ObjectRef<'_>> {
         self.slab.get(addr.into_bits())
     }
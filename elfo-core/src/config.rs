@@ -1,28 +1,90 @@
 use std::{
     any::{Any, TypeId},
-    fmt, mem,
+    cell::RefCell,
+    collections::{btree_map, BTreeMap},
+    fmt,
+    marker::PhantomData,
+    mem,
     ops::Deref,
     sync::Arc,
+    vec,
 };
 
 use derive_more::From;
-use serde::{de, de::value::Error as DeError, Deserialize, Deserializer, Serialize, Serializer};
+use fxhash::FxHashMap;
+use serde::{
+    de::{
+        self,
+        value::{
+            BorrowedBytesDeserializer, BorrowedStrDeserializer, BytesDeserializer,
+            Error as DeError, MapAccessDeserializer, SeqAccessDeserializer,
+        },
+        IntoDeserializer,
+    },
+    Deserialize, Deserializer, Serialize, Serializer,
+};
 use serde_value::{Value, ValueDeserializer};
 
 use crate::local::Local;
 
+#[cfg(feature = "schema")]
+#[cfg_attr(docsrs, doc(cfg(feature = "schema")))]
+pub use schemars;
+
+/// Configs are decoded from groups' sections without the reserved `overrides`
+/// table (see per-key overrides), so it cannot be a field of any config.
 pub trait Config: for<'de> Deserialize<'de> + Send + Sync + fmt::Debug + 'static {}
 impl<C> Config for C where C: for<'de> Deserialize<'de> + Send + Sync + fmt::Debug + 'static {}
 
 assert_impl_all!((): Config);
 
+/// The reserved key of a group's section containing per-key overrides:
+/// ```toml
+/// [shards]
+/// capacity = 100
+/// [shards.overrides."eu-1"]
+/// capacity = 200
+/// ```
+/// Overrides are merged into the rest of the section by keys and used for
+/// actors with the corresponding key (compared with the key's `Display`).
+const OVERRIDES_KEY: &str = "overrides";
+
 #[derive(Clone)]
 pub struct AnyConfig {
     raw: Arc<Value>,
-    // Actually, we store `Arc<Arc<C>>` here.
+    // Actually, we store `Decoded<C>` here.
     decoded: Option<Local<Arc<dyn Any + Send + Sync>>>,
 }
 
+struct Decoded<C> {
+    /// The config for the current actor, the base one by default.
+    config: Arc<C>,
+    base: Effective<C>,
+    overrides: Arc<FxHashMap<String, Effective<C>>>,
+}
+
+impl<C> Decoded<C> {
+    fn effective(&self, key: &str) -> &Effective<C> {
+        self.overrides.get(key).unwrap_or(&self.base)
+    }
+}
+
+/// The config that some actor runs with.
+struct Effective<C> {
+    config: Arc<C>,
+    /// The hash of the raw config, used to detect changes.
+    hash: u64,
+}
+
+impl<C> Clone for Effective<C> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            hash: self.hash,
+        }
+    }
+}
+
 impl AnyConfig {
     pub fn new(value: Value) -> Self {
         Self {
@@ -32,22 +94,77 @@ impl AnyConfig {
     }
 
     pub(crate) fn get<C: 'static>(&self) -> Option<&Arc<C>> {
+        self.get_decoded().map(|decoded| &decoded.config)
+    }
+
+    /// Returns the config for the actor with the provided key.
+    pub(crate) fn get_for_key<C: 'static>(&self, key: &str) -> Option<&Arc<C>> {
+        let decoded = self.get_decoded::<C>()?;
+        Some(&decoded.effective(key).config)
+    }
+
+    /// Returns the hash of the effective config for the actor with the key.
+    pub(crate) fn hash_for_key<C: 'static>(&self, key: &str) -> Option<u64> {
+        let decoded = self.get_decoded::<C>()?;
+        Some(decoded.effective(key).hash)
+    }
+
+    pub(crate) fn has_overrides<C: 'static>(&self) -> bool {
+        matches!(self.get_decoded::<C>(), Some(decoded) if !decoded.overrides.is_empty())
+    }
+
+    /// Returns the same config, but `get()` returns the config for the key.
+    pub(crate) fn for_key<C: Config>(&self, key: &str) -> AnyConfig {
+        let decoded = ward!(self.get_decoded::<C>(), return self.clone());
+
+        let decoded = Decoded {
+            config: decoded.effective(key).config.clone(),
+            base: decoded.base.clone(),
+            overrides: decoded.overrides.clone(),
+        };
+
+        AnyConfig {
+            raw: self.raw.clone(),
+            decoded: Some(Local::from(Arc::new(decoded) as Arc<_>)),
+        }
+    }
+
+    fn get_decoded<C: 'static>(&self) -> Option<&Decoded<C>> {
         self.decoded.as_ref().and_then(|local| local.downcast_ref())
     }
 
     pub(crate) fn decode<C: Config>(&self) -> Result<AnyConfig, String> {
         // Handle the special case of default config.
         let decoded = if TypeId::of::<C>() == TypeId::of::<()>() {
-            Arc::new(Arc::new(())) as Arc<_>
+            let base = Effective::<C>::decode(Value::Unit)?;
+            Decoded {
+                config: base.config.clone(),
+                base,
+                overrides: Default::default(),
+            }
         } else {
-            let de = ValueDeserializer::<DeError>::new((*self.raw).clone());
-            let config = C::deserialize(de).map_err(|err| err.to_string())?;
-            Arc::new(Arc::new(config)) as Arc<_>
+            let (base, patches) = split_overrides((*self.raw).clone())?;
+
+            let mut overrides = FxHashMap::default();
+            for (key, patch) in patches {
+                let mut config = base.clone();
+                merge(&mut config, patch);
+                let effective = Effective::decode(config)
+                    .map_err(|err| format!("{}.{}: {}", OVERRIDES_KEY, key, err))?;
+                overrides.insert(key, effective);
+            }
+
+            let base = Effective::<C>::decode(base)?;
+            Decoded {
+                config: base.config.clone(),
+                base,
+                overrides: Arc::new(overrides),
+            }
         };
 
         Ok(AnyConfig {
             raw: self.raw.clone(),
-            decoded: Some(Local::from(decoded)),
+            decoded: Some(Local::from(Arc::new(decoded) as Arc<_>)),
         })
     }
 
@@ -56,6 +173,62 @@ impl AnyConfig {
     }
 }
 
+impl<C: Config> Effective<C> {
+    fn decode(value: Value) -> Result<Self, String> {
+        let hash = fxhash::hash64(&value);
+        let de = ValueDeserializer::<DeError>::new(value);
+        let config = C::deserialize(de).map_err(|err| err.to_string())?;
+
+        Ok(Self {
+            config: Arc::new(config),
+            hash,
+        })
+    }
+}
+
+/// Splits the raw section into the base config and per-key patches.
+fn split_overrides(mut raw: Value) -> Result<(Value, Vec<(String, Value)>), String> {
+    let overrides = match &mut raw {
+        Value::Map(map) => map.remove(&Value::String(OVERRIDES_KEY.into())),
+        _ => None,
+    };
+
+    let overrides = match overrides {
+        Some(Value::Map(overrides)) => overrides,
+        Some(_) => return Err(format!("`{}` must be a table", OVERRIDES_KEY)),
+        None => return Ok((raw, Vec::new())),
+    };
+
+    let patches = overrides
+        .into_iter()
+        .map(|(key, patch)| match key {
+            Value::String(key) => Ok((key, patch)),
+            _ => Err(format!("`{}` must contain only string keys", OVERRIDES_KEY)),
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok((raw, patches))
+}
+
+/// Merges `src` into `dst` recursively by keys.
+/// Everything except maps is replaced as a whole, including lists.
+#[doc(hidden)]
+pub fn merge(dst: &mut Value, src: Value) {
+    match (dst, src) {
+        (Value::Map(dst), Value::Map(src)) => {
+            for (key, value) in src {
+                match dst.get_mut(&key) {
+                    Some(node) => merge(node, value),
+                    None => {
+                        dst.insert(key, value);
+                    }
+                }
+            }
+        }
+        (dst, src) => *dst = src,
+    }
+}
+
 impl Default for AnyConfig {
     fn default() -> Self {
         Self::new(Value::Map(Default::default()))
@@ -146,15 +319,782 @@ impl<T> fmt::Display for Secret<T> {
     }
 }
 
+// Used to find secrets in raw configs, see `Tracker`.
+const SECRET_NAME: &str = "elfo::Secret";
+pub(crate) const SECRET_PLACEHOLDER: &str = "<secret>";
+
 impl<'de, T: Deserialize<'de>> Deserialize<'de> for Secret<T> {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
-        T::deserialize(deserializer).map(Self)
+        // Secrets are requested as newtypes in order to be found by `Tracker`,
+        // but they're transparent for any deserializer: if it doesn't support
+        // newtypes (e.g. forwards them to `deserialize_any()`), the visited
+        // data is passed to `T` as is.
+        deserializer.deserialize_newtype_struct(SECRET_NAME, SecretVisitor(PhantomData))
+    }
+}
+
+struct SecretVisitor<T>(PhantomData<T>);
+
+macro_rules! forward_to_inner {
+    ($($method:ident($ty:ty);)*) => {$(
+        fn $method<E: de::Error>(self, v: $ty) -> Result<Self::Value, E> {
+            T::deserialize(v.into_deserializer()).map(Secret)
+        }
+    )*};
+}
+
+impl<'de, T: Deserialize<'de>> de::Visitor<'de> for SecretVisitor<T> {
+    type Value = Secret<T>;
+
+    forward_to_inner! {
+        visit_bool(bool);
+        visit_i8(i8);
+        visit_i16(i16);
+        visit_i32(i32);
+        visit_i64(i64);
+        visit_u8(u8);
+        visit_u16(u16);
+        visit_u32(u32);
+        visit_u64(u64);
+        visit_f32(f32);
+        visit_f64(f64);
+        visit_char(char);
+        visit_str(&str);
+        visit_string(String);
+    }
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a secret")
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Self::Value, E> {
+        T::deserialize(BorrowedStrDeserializer::new(v)).map(Secret)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        T::deserialize(BytesDeserializer::new(v)).map(Secret)
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, v: &'de [u8]) -> Result<Self::Value, E> {
+        T::deserialize(BorrowedBytesDeserializer::new(v)).map(Secret)
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        T::deserialize(BytesDeserializer::new(&v)).map(Secret)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Self::Value, E> {
+        T::deserialize(().into_deserializer()).map(Secret)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        T::deserialize(SomeDeserializer(deserializer)).map(Secret)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Self::Value, E> {
+        T::deserialize(().into_deserializer()).map(Secret)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Self::Value, D::Error> {
+        T::deserialize(deserializer).map(Secret)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+        T::deserialize(SeqAccessDeserializer::new(seq)).map(Secret)
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        T::deserialize(MapAccessDeserializer::new(map)).map(Secret)
+    }
+
+    fn visit_enum<A: de::EnumAccess<'de>>(self, data: A) -> Result<Self::Value, A::Error> {
+        T::deserialize(EnumDeserializer(data)).map(Secret)
+    }
+}
+
+/// Passes `Some` to the visitor, used to forward options to `Secret`'s inner
+/// type.
+struct SomeDeserializer<D>(D);
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for SomeDeserializer<D> {
+    type Error = D::Error;
+
+    serde::forward_to_deserialize_any! {
+        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit option
+        seq bytes byte_buf map unit_struct newtype_struct
+        tuple_struct struct tuple enum ignored_any identifier
+    }
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_some(self.0)
+    }
+}
+
+/// Passes the enum to the visitor, used to forward enums to `Secret`'s inner
+/// type.
+struct EnumDeserializer<A>(A);
+
+impl<'de, A: de::EnumAccess<'de>> Deserializer<'de> for EnumDeserializer<A> {
+    type Error = A::Error;
+
+    serde::forward_to_deserialize_any! {
+        bool u8 u16 u32 u64 i8 i16 i32 i64 f32 f64 char str string unit option
+        seq bytes byte_buf map unit_struct newtype_struct
+        tuple_struct struct tuple enum ignored_any identifier
+    }
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_enum(self.0)
     }
 }
 
 impl<T: Serialize> Serialize for Secret<T> {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         // TODO: it should depend on the context (network or dumping).
-        serializer.serialize_str("<secret>")
+        serializer.serialize_str(SECRET_PLACEHOLDER)
+    }
+}
+
+#[cfg(feature = "schema")]
+impl<T: schemars::JsonSchema> schemars::JsonSchema for Secret<T> {
+    fn is_referenceable() -> bool {
+        false
+    }
+
+    fn schema_name() -> String {
+        T::schema_name()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        gen.subschema_for::<T>()
+    }
+}
+
+/// Type-erased operations on configs of some group.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ConfigVTable {
+    pub(crate) type_id: TypeId,
+    pub(crate) validate: fn(&AnyConfig) -> Result<(), String>,
+    pub(crate) redact: fn(Value) -> Value,
+    /// Set by `ActorGroup::with_config_schema()`.
+    #[cfg(feature = "schema")]
+    pub(crate) schema: Option<SchemaFn>,
+}
+
+#[cfg(feature = "schema")]
+pub(crate) type SchemaFn = fn(&mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema;
+
+impl ConfigVTable {
+    pub(crate) fn new<C: Config>() -> Self {
+        Self {
+            type_id: TypeId::of::<C>(),
+            validate: validate::<C>,
+            redact: redact::<C>,
+            #[cfg(feature = "schema")]
+            schema: None,
+        }
+    }
+}
+
+/// Combines schemas of groups into one, placing them by groups' paths,
+/// e.g. the `system.loggers` group is described by `[system.loggers]`.
+#[cfg(feature = "schema")]
+pub(crate) fn combine_schemas(
+    groups: impl Iterator<Item = (String, SchemaFn)>,
+) -> schemars::schema::RootSchema {
+    use schemars::{
+        gen::SchemaSettings,
+        schema::{InstanceType, RootSchema, Schema, SchemaObject},
+    };
+
+    fn section() -> SchemaObject {
+        SchemaObject {
+            instance_type: Some(InstanceType::Object.into()),
+            ..Default::default()
+        }
+    }
+
+    let mut gen = SchemaSettings::draft07().into_generator();
+    let mut root = section();
+
+    'groups: for (name, schema_fn) in groups {
+        let schema = schema_fn(&mut gen);
+        let mut parts = name.split('.').collect::<Vec<_>>();
+        let last = parts.pop().expect("empty group name");
+
+        let mut node = &mut root;
+        for part in parts {
+            let child = node
+                .object()
+                .properties
+                .entry(part.to_owned())
+                .or_insert_with(|| Schema::Object(section()));
+
+            node = match child {
+                Schema::Object(object) => object,
+                // Nested groups cannot be described by boolean schemas.
+                Schema::Bool(_) => continue 'groups,
+            };
+        }
+
+        node.object().properties.insert(last.to_owned(), schema);
+    }
+
+    RootSchema {
+        meta_schema: gen.settings().meta_schema.clone(),
+        schema: root,
+        definitions: gen.take_definitions(),
+    }
+}
+
+/// The same decoding that is used to handle `ValidateConfig` messages.
+fn validate<C: Config>(config: &AnyConfig) -> Result<(), String> {
+    config.decode::<C>().map(drop)
+}
+
+/// Replaces all `Secret` fields of the raw config with `"<secret>"`.
+///
+/// Secrets are found by deserializing `C` with tracking paths. Some secrets
+/// cannot be tracked, e.g. inside enums or `#[serde(flatten)]` fields, so
+/// the whole config is hidden if it contains enums with data or fields
+/// deserialized without type hints (`deserialize_any()`).
+fn redact<C: Config>(config: Value) -> Value {
+    if TypeId::of::<C>() == TypeId::of::<()>() {
+        return config;
+    }
+
+    let hidden = || Value::String(SECRET_PLACEHOLDER.into());
+    let (base, patches) = ward!(split_overrides(config).ok(), return hidden());
+
+    let mut redacted = base.clone();
+    let paths = ward!(find_secrets::<C>(base.clone()), return hidden());
+    hide_secrets(&mut redacted, &paths);
+
+    if patches.is_empty() {
+        return redacted;
+    }
+
+    // Secrets in patches are found in merged configs.
+    let mut overrides = BTreeMap::new();
+    for (key, mut patch) in patches {
+        let mut merged = base.clone();
+        merge(&mut merged, patch.clone());
+        let paths = ward!(find_secrets::<C>(merged), return hidden());
+        hide_secrets(&mut patch, &paths);
+        overrides.insert(Value::String(key), patch);
+    }
+
+    if let Value::Map(map) = &mut redacted {
+        map.insert(Value::String(OVERRIDES_KEY.into()), Value::Map(overrides));
+    }
+
+    redacted
+}
+
+/// Returns `None` if the config is invalid or some secrets cannot be tracked.
+fn find_secrets<C: Config>(config: Value) -> Option<Vec<Vec<PathSegment>>> {
+    let found = RefCell::new(Found::default());
+    let tracker = Tracker {
+        value: config,
+        path: Vec::new(),
+        found: &found,
+    };
+
+    let is_ok = C::deserialize(tracker).is_ok();
+    let found = found.into_inner();
+
+    if is_ok && !found.is_partial {
+        Some(found.paths)
+    } else {
+        None
+    }
+}
+
+fn hide_secrets(config: &mut Value, paths: &[Vec<PathSegment>]) {
+    for path in paths {
+        if let Some(node) = get_mut_by_path(config, path) {
+            *node = Value::String(SECRET_PLACEHOLDER.into());
+        }
+    }
+}
+
+#[derive(Clone)]
+enum PathSegment {
+    Key(Value),
+    Index(usize),
+}
+
+fn get_mut_by_path<'a>(mut node: &'a mut Value, path: &[PathSegment]) -> Option<&'a mut Value> {
+    for segment in path {
+        // Options and newtypes are transparent for the tracker.
+        while let Value::Option(Some(inner)) | Value::Newtype(inner) = node {
+            node = inner;
+        }
+
+        node = match (node, segment) {
+            (Value::Map(map), PathSegment::Key(key)) => map.get_mut(key)?,
+            (Value::Seq(seq), PathSegment::Index(index)) => seq.get_mut(*index)?,
+            _ => return None,
+        };
+    }
+
+    Some(node)
+}
+
+#[derive(Default)]
+struct Found {
+    paths: Vec<Vec<PathSegment>>,
+    /// Some data is deserialized bypassing the tracker, e.g. buffered by
+    /// `#[serde(flatten)]` or enums, so secrets in it cannot be found.
+    is_partial: bool,
+}
+
+/// A deserializer that collects paths to secrets.
+struct Tracker<'a> {
+    value: Value,
+    path: Vec<PathSegment>,
+    found: &'a RefCell<Found>,
+}
+
+/// Like `forward_to_deserialize_any!`, but for known types of data.
+macro_rules! forward_to_visit {
+    ($($method:ident)*) => {$(
+        fn $method<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+            self.visit(visitor)
+        }
+    )*};
+}
+
+impl<'a> Tracker<'a> {
+    fn visit<'de, V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, DeError> {
+        match self.value {
+            Value::Map(map) => visitor.visit_map(TrackerMap {
+                iter: map.into_iter(),
+                pending: None,
+                path: self.path,
+                found: self.found,
+            }),
+            Value::Seq(seq) => visitor.visit_seq(TrackerSeq {
+                iter: seq.into_iter().enumerate(),
+                path: self.path,
+                found: self.found,
+            }),
+            Value::Option(Some(inner)) => visitor.visit_some(Tracker {
+                value: *inner,
+                ..self
+            }),
+            Value::Newtype(inner) => visitor.visit_newtype_struct(Tracker {
+                value: *inner,
+                ..self
+            }),
+            value => ValueDeserializer::<DeError>::new(value).deserialize_any(visitor),
+        }
+    }
+
+    fn mark_partial(&self) {
+        self.found.borrow_mut().is_partial = true;
+    }
+}
+
+impl<'de, 'a> Deserializer<'de> for Tracker<'a> {
+    type Error = DeError;
+
+    forward_to_visit! {
+        deserialize_bool deserialize_u8 deserialize_u16 deserialize_u32 deserialize_u64
+        deserialize_i8 deserialize_i16 deserialize_i32 deserialize_i64 deserialize_f32
+        deserialize_f64 deserialize_char deserialize_str deserialize_string deserialize_unit
+        deserialize_bytes deserialize_byte_buf deserialize_identifier deserialize_seq
+        deserialize_map
+    }
+
+    fn deserialize_any<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        // Data is requested without a type hint if it's buffered, e.g. by
+        // `#[serde(flatten)]`, and then deserialized bypassing the tracker.
+        self.mark_partial();
+        self.visit(visitor)
+    }
+
+    fn deserialize_unit_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.visit(visitor)
+    }
+
+    fn deserialize_tuple<V: de::Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.visit(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.visit(visitor)
+    }
+
+    fn deserialize_struct<V: de::Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.visit(visitor)
+    }
+
+    fn deserialize_ignored_any<V: de::Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        ValueDeserializer::<DeError>::new(self.value).deserialize_ignored_any(visitor)
+    }
+
+    fn deserialize_option<V: de::Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.value {
+            Value::Option(None) => visitor.visit_none(),
+            Value::Option(Some(inner)) => visitor.visit_some(Tracker {
+                value: *inner,
+                ..self
+            }),
+            Value::Unit => visitor.visit_unit(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_enum<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        // Secrets inside enums aren't tracked, only unit variants are safe.
+        if !matches!(self.value, Value::String(_)) {
+            self.mark_partial();
+        }
+
+        ValueDeserializer::<DeError>::new(self.value).deserialize_enum(name, variants, visitor)
+    }
+
+    fn deserialize_newtype_struct<V: de::Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if name == SECRET_NAME {
+            self.found.borrow_mut().paths.push(self.path.clone());
+        }
+
+        match self.value {
+            Value::Newtype(inner) => visitor.visit_newtype_struct(Tracker {
+                value: *inner,
+                ..self
+            }),
+            _ => visitor.visit_newtype_struct(self),
+        }
+    }
+}
+
+struct TrackerMap<'a> {
+    iter: btree_map::IntoIter<Value, Value>,
+    pending: Option<(Value, Value)>,
+    path: Vec<PathSegment>,
+    found: &'a RefCell<Found>,
+}
+
+impl<'de, 'a> de::MapAccess<'de> for TrackerMap<'a> {
+    type Error = DeError;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        let (key, value) = ward!(self.iter.next(), return Ok(None));
+        self.pending = Some((key.clone(), value));
+        seed.deserialize(ValueDeserializer::<DeError>::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        let (key, value) = self
+            .pending
+            .take()
+            .ok_or_else(|| de::Error::custom("value is missing"))?;
+
+        let mut path = self.path.clone();
+        path.push(PathSegment::Key(key));
+
+        seed.deserialize(Tracker {
+            value,
+            path,
+            found: self.found,
+        })
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct TrackerSeq<'a> {
+    iter: std::iter::Enumerate<vec::IntoIter<Value>>,
+    path: Vec<PathSegment>,
+    found: &'a RefCell<Found>,
+}
+
+impl<'de, 'a> de::SeqAccess<'de> for TrackerSeq<'a> {
+    type Error = DeError;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Self::Error> {
+        let (index, value) = ward!(self.iter.next(), return Ok(None));
+
+        let mut path = self.path.clone();
+        path.push(PathSegment::Index(index));
+
+        seed.deserialize(Tracker {
+            value,
+            path,
+            found: self.found,
+        })
+        .map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde::Deserialize;
+
+    fn toml(s: &str) -> Value {
+        toml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn merge_should_override_by_path() {
+        let mut config = toml(
+            r#"
+                [a]
+                x = 1
+                y = [1, 2]
+                [a.b]
+                z = "base"
+            "#,
+        );
+
+        merge(
+            &mut config,
+            toml(
+                r#"
+                    [a]
+                    y = [3]
+                    [a.b]
+                    w = true
+                    [c]
+                    x = 2
+                "#,
+            ),
+        );
+
+        let expected = toml(
+            r#"
+                [a]
+                x = 1
+                y = [3]
+                [a.b]
+                z = "base"
+                w = true
+                [c]
+                x = 2
+            "#,
+        );
+
+        assert_eq!(config, expected);
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    struct Inner {
+        token: Secret<String>,
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    struct Outer {
+        host: String,
+        password: Secret<String>,
+        backup: Option<Inner>,
+        replicas: Vec<Inner>,
+    }
+
+    #[test]
+    fn secrets_should_be_redacted() {
+        let raw = toml(
+            r#"
+                host = "localhost"
+                password = "qwerty"
+                unknown = "field"
+                backup = { token = "a" }
+                replicas = [{ token = "b" }, { token = "c" }]
+            "#,
+        );
+
+        let expected = toml(
+            r#"
+                host = "localhost"
+                password = "<secret>"
+                unknown = "field"
+                backup = { token = "<secret>" }
+                replicas = [{ token = "<secret>" }, { token = "<secret>" }]
+            "#,
+        );
+
+        assert_eq!(redact::<Outer>(raw), expected);
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    struct Flattened {
+        #[serde(flatten)]
+        inner: Inner,
+    }
+
+    #[test]
+    fn untracked_secrets_should_hide_everything() {
+        let raw = toml(r#"token = "a""#);
+        assert_eq!(
+            redact::<Flattened>(raw),
+            Value::String(SECRET_PLACEHOLDER.into())
+        );
+    }
+
+    #[test]
+    fn secrets_should_be_transparent() {
+        let secret = Secret::<u32>::deserialize(IntoDeserializer::<DeError>::into_deserializer(42));
+        assert_eq!(secret.unwrap().into_inner(), 42);
+
+        let value = toml(r#"token = "a""#);
+        let inner = Inner::deserialize(ValueDeserializer::<DeError>::new(value)).unwrap();
+        assert_eq!(*inner.token, "a");
+
+        let secret = Secret::<Option<String>>::deserialize(Value::Option(None)).unwrap();
+        assert_eq!(*secret, None);
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    enum Auth {
+        Anonymous,
+        Token(Inner),
+    }
+
+    #[allow(dead_code)]
+    #[derive(Debug, Deserialize)]
+    struct WithEnum {
+        auth: Auth,
+    }
+
+    #[test]
+    fn secrets_in_enums_should_hide_everything() {
+        let raw = toml(r#"auth = "Anonymous""#);
+        assert_eq!(redact::<WithEnum>(raw.clone()), raw);
+
+        let raw = toml(r#"auth = { Token = { token = "a" } }"#);
+        assert_eq!(
+            redact::<WithEnum>(raw),
+            Value::String(SECRET_PLACEHOLDER.into())
+        );
+    }
+
+    #[test]
+    fn invalid_config_should_be_hidden() {
+        let raw = toml(r#"host = 42"#);
+        assert_eq!(
+            redact::<Outer>(raw),
+            Value::String(SECRET_PLACEHOLDER.into())
+        );
+    }
+
+    #[test]
+    fn overrides_should_be_merged_into_base() {
+        let raw = toml(
+            r#"
+                host = "localhost"
+                password = "qwerty"
+                replicas = []
+                [overrides.a]
+                host = "remote"
+            "#,
+        );
+
+        let config = AnyConfig::new(raw).decode::<Outer>().unwrap();
+        assert_eq!(config.get::<Outer>().unwrap().host, "localhost");
+        assert_eq!(config.get_for_key::<Outer>("a").unwrap().host, "remote");
+        assert_eq!(config.get_for_key::<Outer>("b").unwrap().host, "localhost");
+        assert_eq!(
+            config.for_key::<Outer>("a").get::<Outer>().unwrap().host,
+            "remote"
+        );
+    }
+
+    #[test]
+    fn invalid_overrides_should_be_rejected() {
+        let raw = toml(
+            r#"
+                host = "localhost"
+                password = "qwerty"
+                replicas = []
+                [overrides.a]
+                host = 42
+            "#,
+        );
+
+        let err = AnyConfig::new(raw).decode::<Outer>().unwrap_err();
+        assert!(err.starts_with("overrides.a: "), "{}", err);
+    }
+
+    #[test]
+    fn secrets_in_overrides_should_be_redacted() {
+        let raw = toml(
+            r#"
+                host = "localhost"
+                password = "qwerty"
+                replicas = []
+                [overrides.a]
+                host = "remote"
+                password = "ytrewq"
+            "#,
+        );
+
+        let expected = toml(
+            r#"
+                host = "localhost"
+                password = "<secret>"
+                replicas = []
+                [overrides.a]
+                host = "remote"
+                password = "<secret>"
+            "#,
+        );
+
+        assert_eq!(redact::<Outer>(raw), expected);
     }
 }
@@ -210,6 +210,9 @@ impl<C, K, S> Context<C, K, S> {
         pin_mut!(mailbox_fut);
         pin_mut!(source_fut);
 
+        #[cfg(feature = "test-util")]
+        let parked = actor.park();
+
         // TODO: reset trace_id for these logs?
         let envelope = select_biased! {
             envelope = mailbox_fut => match envelope {
@@ -229,10 +232,13 @@ impl<C, K, S> Context<C, K, S> {
             },
         };
 
+        #[cfg(feature = "test-util")]
+        drop(parked);
+
         tls::set_trace_id(envelope.trace_id());
 
         let envelope = msg!(match envelope {
-            (messages::UpdateConfig { config }, token) => {
+            (messages::UpdateConfig { config, .. }, token) => {
                 self.config = config.get().cloned().expect("must be decoded");
                 info!("config updated");
                 let message = messages::ConfigUpdated {};
@@ -272,7 +278,7 @@ impl<C, K, S> Context<C, K, S> {
         // TODO: poll the sources.
 
         let envelope = msg!(match envelope {
-            (messages::UpdateConfig { config }, token) => {
+            (messages::UpdateConfig { config, .. }, token) => {
                 self.config = config.get().cloned().expect("must be decoded");
                 info!("config updated");
                 let message = messages::ConfigUpdated {};
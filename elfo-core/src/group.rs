@@ -3,10 +3,11 @@ use std::{fmt::Debug, future::Future, marker::PhantomData, sync::Arc};
 use smallbox::smallbox;
 
 use crate::{
-    config::Config,
+    actor::{ActorStatus, StatusHooks},
+    config::{Config, ConfigVTable},
     context::Context,
     exec::ExecResult,
-    object::{Group, Object},
+    object::{Group, Object, ObjectMeta},
     routers::Router,
     supervisor::Supervisor,
 };
@@ -14,6 +15,8 @@ use crate::{
 #[derive(Debug)]
 pub struct ActorGroup<R, C> {
     router: R,
+    config: ConfigVTable,
+    status_hooks: StatusHooks,
     _config: PhantomData<C>,
 }
 
@@ -22,6 +25,8 @@ impl ActorGroup<(), ()> {
     pub fn new() -> Self {
         Self {
             router: (),
+            config: ConfigVTable::new::<()>(),
+            status_hooks: StatusHooks::default(),
             _config: PhantomData,
         }
     }
@@ -31,17 +36,45 @@ impl<R, C> ActorGroup<R, C> {
     pub fn config<C1: Config>(self) -> ActorGroup<R, C1> {
         ActorGroup {
             router: self.router,
+            config: ConfigVTable::new::<C1>(),
+            status_hooks: self.status_hooks,
             _config: PhantomData,
         }
     }
 
+    /// Records the JSON Schema of the config in order to export it by
+    /// `Topology::config_schema()`. Should be called after `config()`.
+    #[cfg(feature = "schema")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "schema")))]
+    pub fn with_config_schema(mut self) -> Self
+    where
+        C: schemars::JsonSchema,
+    {
+        self.config.schema = Some(|gen| gen.subschema_for::<C>());
+        self
+    }
+
     pub fn router<R1: Router<C>>(self, router: R1) -> ActorGroup<R1, C> {
         ActorGroup {
             router,
+            config: self.config,
+            status_hooks: self.status_hooks,
             _config: self._config,
         }
     }
 
+    /// Registers the hook called on every change of statuses of all actors
+    /// in the topology the group is mounted to, before the change is logged.
+    /// Hooks are called in the actor's scope and must neither block nor
+    /// change statuses. They are dropped with the topology.
+    pub fn on_status_changed(
+        self,
+        hook: impl Fn(&Arc<ObjectMeta>, &ActorStatus) + Send + Sync + 'static,
+    ) -> Self {
+        self.status_hooks.add(hook);
+        self
+    }
+
     pub fn exec<X, O, ER>(self, exec: X) -> Schema
     where
         R: Router<C>,
@@ -50,17 +83,25 @@ impl<R, C> ActorGroup<R, C> {
         ER: ExecResult,
         C: Config,
     {
-        let run = move |ctx: Context, name: String| {
+        let config = self.config;
+        let router = self.router;
+        let run = move |ctx: Context, name: String, status_hooks: StatusHooks| {
             let addr = ctx.addr();
-            let sv = Arc::new(Supervisor::new(ctx, name, exec, self.router));
+            let sv = Arc::new(Supervisor::new(ctx, name, exec, router, status_hooks));
             let router = smallbox!(move |envelope| { sv.handle(envelope) });
             Object::new(addr, Group::new(router))
         };
 
-        Schema { run: Box::new(run) }
+        Schema {
+            run: Box::new(run),
+            config,
+            status_hooks: self.status_hooks,
+        }
     }
 }
 
 pub struct Schema {
-    pub(crate) run: Box<dyn FnOnce(Context, String) -> Object>,
+    pub(crate) run: Box<dyn FnOnce(Context, String, StatusHooks) -> Object>,
+    pub(crate) config: ConfigVTable,
+    pub(crate) status_hooks: StatusHooks,
 }
@@ -31,6 +31,8 @@ pub mod tls;
 pub mod topology;
 pub mod trace_id;
 
+#[cfg(feature = "test-util")]
+mod activity;
 mod actor;
 mod addr;
 mod address_book;
@@ -51,11 +53,14 @@ mod supervisor;
 #[doc(hidden)]
 pub mod _priv {
     pub use crate::{
+        config::merge,
         envelope::{AnyMessageBorrowed, AnyMessageOwned, EnvelopeBorrowed, EnvelopeOwned},
         message::{AnyMessage, LocalTypeId, MessageVTable, MESSAGE_LIST},
         object::ObjectMeta,
         start::do_start,
     };
+    #[cfg(feature = "test-util")]
+    pub use crate::activity::{book_id, current_book_id, is_group_idle};
     pub use linkme;
     pub use serde;
     pub use smallbox;
@@ -1,3 +1,6 @@
+#[cfg(feature = "test-util")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use futures_intrusive::{
     buffer::GrowingHeapBuf,
     channel::{self, GenericChannel},
@@ -14,36 +17,80 @@ const LIMIT: usize = 100_000;
 
 pub(crate) struct Mailbox {
     queue: GenericChannel<RawMutex, Envelope, GrowingHeapBuf<Envelope>>,
+    /// Includes envelopes of blocked senders, the channel doesn't provide it.
+    #[cfg(feature = "test-util")]
+    len: AtomicUsize,
 }
 
 impl Mailbox {
     pub(crate) fn new() -> Self {
         Self {
             queue: GenericChannel::with_capacity(LIMIT),
+            #[cfg(feature = "test-util")]
+            len: AtomicUsize::new(0),
         }
     }
 
     pub(crate) async fn send(&self, envelope: Envelope) -> Result<(), SendError<Envelope>> {
+        #[cfg(feature = "test-util")]
+        self.len.fetch_add(1, Ordering::SeqCst);
+
         let fut = self.queue.send(envelope);
-        fut.await.map_err(|err| SendError(err.0))
+        let result = fut.await.map_err(|err| SendError(err.0));
+
+        #[cfg(feature = "test-util")]
+        if result.is_err() {
+            self.len.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        result
     }
 
     pub(crate) fn try_send(&self, envelope: Envelope) -> Result<(), TrySendError<Envelope>> {
-        self.queue.try_send(envelope).map_err(|err| match err {
+        #[cfg(feature = "test-util")]
+        self.len.fetch_add(1, Ordering::SeqCst);
+
+        let result = self.queue.try_send(envelope).map_err(|err| match err {
             channel::TrySendError::Full(envelope) => TrySendError::Full(envelope),
             channel::TrySendError::Closed(envelope) => TrySendError::Closed(envelope),
-        })
+        });
+
+        #[cfg(feature = "test-util")]
+        if result.is_err() {
+            self.len.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        result
     }
 
     pub(crate) async fn recv(&self) -> Option<Envelope> {
         let fut = self.queue.receive();
-        fut.await
+        let envelope = fut.await;
+
+        #[cfg(feature = "test-util")]
+        if envelope.is_some() {
+            self.len.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        envelope
     }
 
     pub(crate) fn try_recv(&self) -> Result<Envelope, TryRecvError> {
-        self.queue.try_receive().map_err(|err| match err {
+        let result = self.queue.try_receive().map_err(|err| match err {
             channel::TryReceiveError::Empty => TryRecvError::Empty,
             channel::TryReceiveError::Closed => TryRecvError::Closed,
-        })
+        });
+
+        #[cfg(feature = "test-util")]
+        if result.is_ok() {
+            self.len.fetch_sub(1, Ordering::SeqCst);
+        }
+
+        result
+    }
+
+    #[cfg(feature = "test-util")]
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len.load(Ordering::SeqCst) == 0
     }
 }
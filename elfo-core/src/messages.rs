@@ -16,9 +16,26 @@ pub struct ValidateConfig {
 }
 
 #[message(ret = Result<(), ConfigRejected>, elfo = crate)]
-#[derive(Constructor)]
 pub struct UpdateConfig {
     pub config: AnyConfig,
+    /// See `UpdateConfig::with_force()`.
+    #[serde(default)]
+    pub force: bool,
+}
+
+impl UpdateConfig {
+    pub fn new(config: AnyConfig) -> Self {
+        Self {
+            config,
+            force: false,
+        }
+    }
+
+    /// By default, actors with up-to-date effective configs are skipped.
+    /// If enabled, all actors will be updated.
+    pub fn with_force(self, force: bool) -> Self {
+        Self { force, ..self }
+    }
 }
 
 #[message(elfo = crate)]
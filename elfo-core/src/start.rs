@@ -4,6 +4,7 @@ use std::{
 };
 
 use futures::TryFutureExt;
+use tracing::error;
 
 use crate::{
     actor::Actor,
@@ -21,12 +22,21 @@ use crate::{
 type Result<T, E = StartError> = std::result::Result<T, E>;
 
 async fn send_configs_to_entrypoints(ctx: &Context, topology: &Topology) -> Result<()> {
+    let mut configs = topology.load_configs().map_err(|reason| {
+        error!(%reason, "cannot load configs of entrypoints");
+        StartError::InvalidConfig
+    })?;
+
     let futures = topology
         .actor_groups()
         .filter(|group| group.is_entrypoint)
         .map(|group| {
-            let config = Default::default();
-            ctx.request(UpdateConfig { config })
+            let config = configs
+                .iter()
+                .position(|(name, _)| *name == group.name)
+                .map(|idx| configs.swap_remove(idx).1)
+                .unwrap_or_default();
+            ctx.request(UpdateConfig::new(config))
                 .from(group.addr)
                 .resolve()
                 .or_else(|err| async move {
@@ -86,14 +96,16 @@ pub async fn do_start<F: Future>(
 
     let entry = topology.book.vacant_entry();
     let addr = entry.addr();
-    entry.insert(Object::new(addr, Actor::new(addr)));
 
-    let meta = ObjectMeta {
+    let meta = Arc::new(ObjectMeta {
         group: "starter".into(),
         key: None,
-    };
+    });
+    let actor = Actor::new(addr, meta.clone(), topology.status_hooks.clone());
+    entry.insert(Object::new(addr, actor));
+
     let initial_trace_id = trace_id::generate();
-    tls::scope(Arc::new(meta), initial_trace_id, async move {
+    tls::scope(meta, initial_trace_id, async move {
         let ctx = Context::new(topology.book.clone(), Demux::default()).with_addr(addr);
         send_configs_to_entrypoints(&ctx, &topology).await?;
         start_entrypoints(&ctx, &topology).await?;
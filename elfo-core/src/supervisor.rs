@@ -1,9 +1,13 @@
-use std::{any::Any, future::Future, panic::AssertUnwindSafe, sync::Arc, time::Duration};
+use std::{
+    any::Any, future::Future, marker::PhantomData, panic::AssertUnwindSafe, sync::Arc,
+    time::Duration,
+};
 
 use dashmap::DashMap;
 use futures::FutureExt;
 use fxhash::FxBuildHasher;
 use parking_lot::RwLock;
+use tokio::time::Instant;
 use tracing::{error_span, info, Instrument, Span};
 
 use crate as elfo;
@@ -11,9 +15,9 @@ use elfo_macros::msg_raw as msg;
 use elfo_utils::{CachePadded, ErrorChain};
 
 use crate::{
-    actor::{Actor, ActorStatus},
+    actor::{Actor, ActorStatus, StatusHooks},
     addr::Addr,
-    config::Config,
+    config::{AnyConfig, Config},
     context::Context,
     envelope::Envelope,
     errors::TrySendError,
@@ -28,25 +32,28 @@ pub(crate) struct Supervisor<R: Router<C>, C, X> {
     meta: Arc<ObjectMeta>,
     span: Span,
     context: Context,
+    status_hooks: StatusHooks,
     // TODO: replace with `crossbeam_utils::sync::ShardedLock`?
     objects: DashMap<R::Key, ObjectArc, FxBuildHasher>,
     router: R,
     exec: X,
-    control: CachePadded<RwLock<ControlBlock<C>>>,
+    control: CachePadded<RwLock<ControlBlock>>,
+    _config: PhantomData<C>,
 }
 
-struct ControlBlock<C> {
-    config: Option<Arc<C>>,
+struct ControlBlock {
+    /// The decoded config, including per-key overrides.
+    config: Option<AnyConfig>,
 }
 
 macro_rules! get_or_spawn {
     ($this:ident, $key:expr) => {{
-        let key = $key;
-        ward!($this.objects.get(&key), {
+        let key: &R::Key = $key;
+        ward!($this.objects.get(key), {
             $this
                 .objects
                 .entry(key.clone())
-                .or_insert_with(|| $this.spawn(key))
+                .or_insert_with(|| $this.spawn(key.clone()))
                 .downgrade()
         })
     }};
@@ -59,17 +66,25 @@ where
     <X::Output as Future>::Output: ExecResult,
     C: Config,
 {
-    pub(crate) fn new(ctx: Context, group: String, exec: X, router: R) -> Self {
+    pub(crate) fn new(
+        ctx: Context,
+        group: String,
+        exec: X,
+        router: R,
+        status_hooks: StatusHooks,
+    ) -> Self {
         let control = ControlBlock { config: None };
 
         Self {
             span: error_span!(parent: Span::none(), "", actor_group = group.as_str()),
             meta: Arc::new(ObjectMeta { group, key: None }),
             context: ctx,
+            status_hooks,
             objects: DashMap::default(),
             router,
             exec,
             control: CachePadded(RwLock::new(control)),
+            _config: PhantomData,
         }
     }
 
@@ -85,8 +100,16 @@ where
                     if !is_first_update {
                         let outcome = self.router.route(&envelope);
                         let mut envelope = envelope;
-                        envelope.set_message(messages::ValidateConfig { config });
-                        self.do_handle(envelope, outcome.or(Outcome::Broadcast))
+                        envelope.set_message(messages::ValidateConfig {
+                            config: config.clone(),
+                        });
+                        self.do_handle_with(envelope, outcome.or(Outcome::Broadcast), |key, e| {
+                            if config.has_overrides::<C>() {
+                                let config = config.for_key::<C>(&key.to_string());
+                                e.set_message(messages::ValidateConfig { config });
+                            }
+                            true
+                        })
                     } else {
                         RouteReport::Done
                     }
@@ -102,22 +125,38 @@ where
                     RouteReport::Done
                 }
             },
-            messages::UpdateConfig { config } => match config.decode::<C>() {
+            messages::UpdateConfig { config, force } => match config.decode::<C>() {
                 Ok(config) => {
+                    let force = *force;
                     let mut control = self.control.write();
-                    let is_first_update = control.config.is_none();
-                    control.config = config.get().cloned();
-                    self.router
-                        .update(&control.config.as_ref().expect("just saved"));
-                    self.in_scope(
-                        || info!(config = ?control.config.as_ref().unwrap(), "router updated"),
-                    );
+                    let old_config = control.config.replace(config.clone());
+                    let base = config.get::<C>().expect("just decoded");
+                    self.router.update(base);
+                    self.in_scope(|| info!(config = ?base, "router updated"));
                     drop(control);
                     let outcome = self.router.route(&envelope);
-                    if !is_first_update {
+                    if let Some(old_config) = old_config {
                         let mut envelope = envelope;
-                        envelope.set_message(messages::UpdateConfig { config });
-                        self.do_handle(envelope, outcome.or(Outcome::Broadcast))
+                        envelope.set_message(messages::UpdateConfig {
+                            config: config.clone(),
+                            force,
+                        });
+                        self.do_handle_with(envelope, outcome.or(Outcome::Broadcast), |key, e| {
+                            let key = key.to_string();
+
+                            // Skip actors whose effective configs haven't changed.
+                            let old_hash = old_config.hash_for_key::<C>(&key);
+                            if !force && old_hash == config.hash_for_key::<C>(&key) {
+                                return false;
+                            }
+
+                            if config.has_overrides::<C>() {
+                                let config = config.for_key::<C>(&key);
+                                e.set_message(messages::UpdateConfig { config, force });
+                            }
+
+                            true
+                        })
                     } else {
                         self.spawn_by_outcome(outcome);
                         RouteReport::Done
@@ -145,11 +184,27 @@ where
         self: &Arc<Self>,
         envelope: Envelope,
         outcome: Outcome<R::Key>,
+    ) -> RouteReport {
+        self.do_handle_with(envelope, outcome, |_, _| true)
+    }
+
+    /// Like `do_handle`, but `prepare` can modify an envelope for each actor
+    /// or skip the actor by returning `false`. Skipped actors are considered
+    /// as handled ones.
+    fn do_handle_with(
+        self: &Arc<Self>,
+        envelope: Envelope,
+        outcome: Outcome<R::Key>,
+        prepare: impl Fn(&R::Key, &mut Envelope) -> bool,
     ) -> RouteReport {
         // TODO: avoid copy & paste.
         match outcome {
             Outcome::Unicast(key) => {
-                let object = get_or_spawn!(self, key);
+                let mut envelope = envelope;
+                let object = get_or_spawn!(self, &key);
+                if !prepare(&key, &mut envelope) {
+                    return RouteReport::Done;
+                }
                 let actor = object.as_actor().expect("supervisor stores only actors");
                 match actor.try_send(envelope) {
                     Ok(()) => RouteReport::Done,
@@ -163,14 +218,19 @@ where
 
                 // TODO: avoid the loop in `try_send` case.
                 for key in list {
-                    let object = get_or_spawn!(self, key);
+                    let object = get_or_spawn!(self, &key);
 
                     // TODO: we shouldn't clone `envelope` for the last object in a sequence.
-                    let envelope = ward!(
+                    let mut envelope = ward!(
                         envelope.duplicate(self.context.book()),
                         continue // A requester has died, but `Multicast` is more insistent.
                     );
 
+                    if !prepare(&key, &mut envelope) {
+                        someone = true;
+                        continue;
+                    }
+
                     let actor = object.as_actor().expect("supervisor stores only actors");
 
                     match actor.try_send(envelope) {
@@ -199,11 +259,16 @@ where
                 // TODO: avoid the loop in `try_send` case.
                 for object in self.objects.iter() {
                     // TODO: we shouldn't clone `envelope` for the last object in a sequence.
-                    let envelope = ward!(
+                    let mut envelope = ward!(
                         envelope.duplicate(self.context.book()),
                         return RouteReport::Done // A requester has died.
                     );
 
+                    if !prepare(object.key(), &mut envelope) {
+                        someone = true;
+                        continue;
+                    }
+
                     let actor = object.as_actor().expect("supervisor stores only actors");
 
                     match actor.try_send(envelope) {
@@ -243,11 +308,16 @@ where
 
         let meta = Arc::new(ObjectMeta {
             group: self.meta.group.clone(),
-            key: Some(key_str),
+            key: Some(key_str.clone()),
         });
 
         let control = self.control.read();
-        let config = control.config.as_ref().cloned().expect("config is unset");
+        let config = control
+            .config
+            .as_ref()
+            .and_then(|config| config.get_for_key::<C>(&key_str))
+            .cloned()
+            .expect("config is unset");
 
         let ctx = self
             .context
@@ -264,6 +334,9 @@ where
         // TODO: protect against panics (for `fn(..) -> impl Future`).
         let fut = self.exec.exec(ctx);
 
+        #[cfg(feature = "test-util")]
+        let activity = crate::activity::track(self.context.book(), self.context.addr(), addr);
+
         // TODO: move to `harness.rs`.
         let fut = async move {
             info!(%addr, "started");
@@ -286,17 +359,26 @@ where
 
             if need_to_restart {
                 // TODO: use `backoff`.
-                tokio::time::sleep(Duration::from_secs(5)).await;
+                let restart_at = Instant::now() + Duration::from_secs(5);
+                #[cfg(feature = "test-util")]
+                activity.restart_at(restart_at);
+                tokio::time::sleep_until(restart_at).await;
                 sv.objects.insert(key.clone(), sv.spawn(key))
             } else {
                 sv.objects.remove(&key).map(|(_, v)| v)
             }
             .expect("where is the current actor?");
 
+            #[cfg(feature = "test-util")]
+            drop(activity);
             sv.context.book().remove(addr);
         };
 
-        entry.insert(Object::new(addr, Actor::new(addr)));
+        #[cfg(feature = "test-util")]
+        let fut = crate::activity::scope(self.context.book(), fut);
+
+        let actor = Actor::new(addr, meta.clone(), self.status_hooks.clone());
+        entry.insert(Object::new(addr, actor));
         let initial_trace_id = trace_id::generate();
         tokio::spawn(tls::scope(meta, initial_trace_id, fut.instrument(span)));
         self.context.book().get_owned(addr).expect("just created")
@@ -305,11 +387,11 @@ where
     fn spawn_by_outcome(self: &Arc<Self>, outcome: Outcome<R::Key>) {
         match outcome {
             Outcome::Unicast(key) => {
-                get_or_spawn!(self, key);
+                get_or_spawn!(self, &key);
             }
             Outcome::Multicast(keys) => {
                 for key in keys {
-                    get_or_spawn!(self, key);
+                    get_or_spawn!(self, &key);
                 }
             }
             Outcome::Broadcast | Outcome::Discard | Outcome::Default => {}
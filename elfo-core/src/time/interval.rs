@@ -59,12 +59,22 @@ impl<F> Interval<F> {
         let old_period = state.period;
         state.period = new_period;
 
-        if state.start_at.is_none() {
+        // Stopped or never configured, so the old deadline is meaningless.
+        if old_period == Duration::new(0, 0) && state.start_at.is_none() {
+            state.sleep.as_mut().reset(Instant::now() + new_period);
+        } else if state.start_at.is_none() {
             let new_deadline = state.sleep.deadline() - old_period + new_period;
             state.sleep.as_mut().reset(new_deadline);
         }
     }
 
+    /// Stops producing messages until `set_period()` is called.
+    pub fn stop(&self) {
+        let mut state = self.state.lock();
+        state.period = Duration::new(0, 0);
+        state.start_at = None;
+    }
+
     pub fn reset(&self) {
         let mut state = self.state.lock();
         let new_deadline = Instant::now() + state.period;
@@ -106,3 +116,44 @@ where
         Poll::Pending
     }
 }
+
+#[cfg(test)]
+#[cfg(feature = "test-util")]
+mod tests {
+    use super::*;
+
+    use futures::{future::poll_fn, poll};
+
+    use elfo_macros::message;
+
+    use crate::time;
+
+    #[message(elfo = crate)]
+    struct Tick;
+
+    #[tokio::test]
+    async fn it_stops_and_restarts() {
+        time::pause();
+
+        let interval = Interval::new(|| Tick);
+        interval.set_period(Duration::from_secs(10));
+
+        time::advance(Duration::from_millis(10001)).await;
+        let res = poll!(poll_fn(|cx| interval.poll_recv(cx)));
+        assert!(res.is_ready());
+
+        interval.stop();
+        time::advance(Duration::from_secs(60)).await;
+        let res = poll!(poll_fn(|cx| interval.poll_recv(cx)));
+        assert!(res.is_pending());
+
+        // The period is counted from restarting, not from the last tick.
+        interval.set_period(Duration::from_secs(10));
+        let res = poll!(poll_fn(|cx| interval.poll_recv(cx)));
+        assert!(res.is_pending());
+
+        time::advance(Duration::from_millis(10001)).await;
+        let res = poll!(poll_fn(|cx| interval.poll_recv(cx)));
+        assert!(res.is_ready());
+    }
+}
@@ -1,10 +1,13 @@
-use std::{cell::RefCell, sync::Arc};
+use std::{any::TypeId, cell::RefCell, sync::Arc};
 
 use parking_lot::RwLock;
+use serde_value::Value;
 
 use crate::{
+    actor::StatusHooks,
     addr::Addr,
     address_book::{AddressBook, VacantEntry},
+    config::{AnyConfig, Config, ConfigVTable, SECRET_PLACEHOLDER},
     context::Context,
     demux::{Demux, Filter},
     envelope::Envelope,
@@ -14,13 +17,18 @@ use crate::{
 #[derive(Clone)]
 pub struct Topology {
     pub(crate) book: AddressBook,
+    pub(crate) status_hooks: StatusHooks,
     inner: Arc<RwLock<Inner>>,
 }
 
+type ConfigLoader =
+    Arc<dyn Fn(&Topology) -> Result<Vec<(String, AnyConfig)>, String> + Send + Sync>;
+
 #[derive(Default)]
 struct Inner {
     groups: Vec<ActorGroup>,
     connections: Vec<Connection>,
+    config_loader: Option<ConfigLoader>,
 }
 
 #[derive(Debug, Clone)]
@@ -29,6 +37,35 @@ pub struct ActorGroup {
     pub addr: Addr,
     pub name: String,
     pub is_entrypoint: bool,
+    // Set when the group is mounted.
+    config: Option<ConfigVTable>,
+}
+
+impl ActorGroup {
+    /// Decodes the config in the same way as the group does it on
+    /// `ValidateConfig`, but without any running actors.
+    pub fn validate_config(&self, config: &AnyConfig) -> Result<(), String> {
+        match &self.config {
+            Some(vtable) => (vtable.validate)(config),
+            None => Err("the group isn't mounted".into()),
+        }
+    }
+
+    /// Returns `true` if the group is mounted with the config of the `C` type.
+    pub fn has_config<C: Config>(&self) -> bool {
+        let type_id = self.config.as_ref().map(|vtable| vtable.type_id);
+        type_id == Some(TypeId::of::<C>())
+    }
+
+    /// Replaces all `Secret` fields of the group's raw config with
+    /// `"<secret>"`. If it's impossible to determine secrets (e.g. the group
+    /// isn't mounted yet), the whole config is hidden.
+    pub fn redact_config(&self, config: Value) -> Value {
+        match &self.config {
+            Some(vtable) => (vtable.redact)(config),
+            None => Value::String(SECRET_PLACEHOLDER.into()),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -42,6 +79,7 @@ impl Topology {
     pub fn empty() -> Self {
         Self {
             book: AddressBook::new(),
+            status_hooks: StatusHooks::default(),
             inner: Arc::new(RwLock::new(Inner::default())),
         }
     }
@@ -55,6 +93,7 @@ impl Topology {
             addr: entry.addr(),
             name: name.clone(),
             is_entrypoint: false,
+            config: None,
         });
 
         Local {
@@ -78,6 +117,39 @@ impl Topology {
         let inner = self.inner.read();
         inner.connections.clone().into_iter()
     }
+
+    /// Sets the function that loads configs of groups (by their names) before
+    /// starting the system. The starter uses it to send real configs to
+    /// entrypoints, because there is no configurer running at that moment.
+    /// It's called by the configurer, e.g. `elfo::configurer::from_path()`.
+    #[doc(hidden)]
+    pub fn set_config_loader(
+        &self,
+        loader: impl Fn(&Topology) -> Result<Vec<(String, AnyConfig)>, String> + Send + Sync + 'static,
+    ) {
+        self.inner.write().config_loader = Some(Arc::new(loader));
+    }
+
+    /// Returns the JSON Schema of the whole config file. Sections are placed
+    /// by groups' paths, e.g. `[system.loggers]`. Only groups mounted with
+    /// `ActorGroup::with_config_schema()` are described.
+    ///
+    /// The schema can be used by editors to validate and autocomplete configs.
+    #[cfg(feature = "schema")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "schema")))]
+    pub fn config_schema(&self) -> schemars::schema::RootSchema {
+        let groups = self
+            .actor_groups()
+            .filter_map(|group| Some((group.name, group.config?.schema?)));
+
+        crate::config::combine_schemas(groups)
+    }
+
+    /// Returns configs provided by the config loader, if it's set.
+    pub(crate) fn load_configs(&self) -> Result<Vec<(String, AnyConfig)>, String> {
+        let loader = self.inner.read().config_loader.clone();
+        loader.map_or_else(|| Ok(Vec::new()), |loader| loader(self))
+    }
 }
 
 impl Default for Topology {
@@ -126,9 +198,22 @@ impl<'t> Local<'t> {
 
     pub fn mount(self, schema: Schema) {
         let addr = self.entry.addr();
+
+        let mut inner = self.topology.inner.write();
+        let group = inner
+            .groups
+            .iter_mut()
+            .find(|group| group.addr == addr)
+            .expect("just created");
+        group.config = Some(schema.config);
+        drop(inner);
+
+        let status_hooks = &self.topology.status_hooks;
+        status_hooks.extend(&schema.status_hooks);
+
         let book = self.topology.book.clone();
         let ctx = Context::new(book, self.demux.into_inner()).with_addr(addr);
-        let object = (schema.run)(ctx, self.name);
+        let object = (schema.run)(ctx, self.name, status_hooks.clone());
         self.entry.insert(object);
     }
 }
@@ -1,23 +1,31 @@
-use std::sync::Arc;
-
-use tokio::{
-    fs::{File, OpenOptions},
-    io::AsyncWriteExt,
+use std::{
+    fmt::Write,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime},
 };
 
 use elfo_core as elfo;
 use elfo_macros::{message, msg_raw as msg};
 
 use elfo::{
+    _priv::ObjectMeta,
     messages::ConfigUpdated,
     signal::{Signal, SignalKind},
+    time::Interval,
     ActorGroup, Context, Schema,
 };
+use tracing::{error, level_filters::LevelFilter, Level};
 
 use crate::{
-    config::{Config, Sink},
-    formatters::Formatter,
-    theme, PreparedEvent, Shared,
+    config::{Config, TimestampConfig},
+    dropped::Dropped,
+    filter::Filter,
+    format::{EventFormatter, EventView, JsonFormatter, LogfmtFormatter, TextFormatter},
+    payload::FieldValue,
+    ring::Status,
+    sink::{SinkWriter, Style},
+    theme::{ColoredTheme, PlainTheme},
+    PreparedEvent, Shared,
 };
 
 pub(crate) struct Logger {
@@ -31,13 +39,26 @@ pub(crate) struct Logger {
 #[derive(Default)]
 pub struct ReopenLogFile {}
 
+#[message(elfo = elfo_core)]
+struct Tick;
+
+const TICK_INTERVAL: Duration = Duration::from_secs(1);
+
 impl Logger {
     // TODO: rename it?
     #[allow(clippy::new_ret_no_self)]
     pub(crate) fn new(shared: Arc<Shared>) -> Schema {
-        ActorGroup::new()
-            .config::<Config>()
-            .exec(move |ctx| Logger::ctor(ctx, shared.clone()).main())
+        let hook_shared = shared.clone();
+        let group =
+            ActorGroup::new()
+                .config::<Config>()
+                .on_status_changed(move |object, status| {
+                    let status = ward!(Status::from_actor_status(status));
+                    hook_shared.on_status_changed(object, status);
+                });
+        #[cfg(feature = "schema")]
+        let group = group.with_config_schema();
+        group.exec(move |ctx| Logger::ctor(ctx, shared.clone()).main())
     }
 
     fn ctor(ctx: Context<Config>, shared: Arc<Shared>) -> Self {
@@ -45,12 +66,18 @@ impl Logger {
     }
 
     async fn main(self) {
-        let mut buffer = String::with_capacity(1024);
-        let mut file = open_file(self.ctx.config()).await;
-        let mut use_colors = can_use_colors(self.ctx.config());
+        let custom = self.shared.formatter.clone();
+        let mut buffers = Buffers::new(custom, self.ctx.config().timestamp);
+        self.update_limiter();
+        self.update_ring_buffers();
+        self.update_trace_chains();
+        self.update_filter();
+        let mut sinks = open_sinks(self.ctx.config(), &buffers).await;
 
         let signal = Signal::new(SignalKind::Hangup, ReopenLogFile::default);
-        let mut ctx = self.ctx.clone().with(&signal);
+        let ticker = Interval::new(|| Tick);
+        ticker.set_period(TICK_INTERVAL);
+        let mut ctx = self.ctx.clone().with(&signal).with(&ticker);
 
         // Note that we don't use `elfo::stream::Stream` here intentionally
         // to avoid cyclic dependences (`Context::recv()` logs all messages).
@@ -58,27 +85,44 @@ impl Logger {
             tokio::select! {
                 event = self.shared.channel.receive() => {
                     let event = event.expect("channel cannot close");
-                    buffer.clear();
-
-                    if use_colors {
-                        self.format_event::<theme::ColoredTheme>(&mut buffer, event);
-                    } else {
-                        self.format_event::<theme::PlainTheme>(&mut buffer, event);
-                    }
-
-                    if let Some(file) = file.as_mut() {
-                        // TODO: what about performance here?
-                        file.write_all(buffer.as_ref()).await.expect("cannot write to the config file");
-                    } else {
-                        print!("{}", buffer);
-                    }
+                    self.write_event(&mut sinks, &mut buffers, event).await;
                 },
                 envelope = ctx.recv() => {
                     let envelope = ward!(envelope, break);
                     msg!(match envelope {
-                        ReopenLogFile | ConfigUpdated => {
-                            file = open_file(self.ctx.config()).await;
-                            use_colors = can_use_colors(self.ctx.config());
+                        ReopenLogFile => {
+                            sinks = open_sinks(self.ctx.config(), &buffers).await;
+                        },
+                        ConfigUpdated => {
+                            buffers.timestamp = self.ctx.config().timestamp;
+                            self.update_limiter();
+                            self.update_ring_buffers();
+                            self.update_trace_chains();
+                            self.update_filter();
+                            sinks = open_sinks(self.ctx.config(), &buffers).await;
+                        },
+                        Tick => {
+                            self.shared.clock.recalibrate();
+
+                            for sink in &mut sinks {
+                                sink.reopen_if_closed().await;
+                            }
+
+                            // Write them directly, because the channel can be still full.
+                            for dropped in self.shared.dropped.take() {
+                                record_metrics(&dropped);
+
+                                if let Some(event) = self.prepare_report(dropped) {
+                                    self.write_event(&mut sinks, &mut buffers, event).await;
+                                }
+                            }
+
+                            let now = Instant::now();
+                            for event in self.shared.limiter.flush(now, &self.shared.pool, &self.shared.dropped) {
+                                self.write_event(&mut sinks, &mut buffers, event).await;
+                            }
+
+                            self.shared.chains.expire(now, &self.shared.pool);
                         },
                     });
                 },
@@ -86,63 +130,482 @@ impl Logger {
         }
     }
 
-    pub(super) fn format_event<T: theme::Theme>(&self, out: &mut String, event: PreparedEvent) {
-        let payload = self
-            .shared
-            .pool
-            .get(event.payload_id)
-            .expect("unknown string");
+    async fn write_event(
+        &self,
+        sinks: &mut [SinkWriter],
+        buffers: &mut Buffers,
+        event: PreparedEvent,
+    ) {
+        buffers.clear();
+
+        let mut is_dropped = false;
+        for sink in sinks.iter_mut().filter(|sink| sink.accepts(event.level)) {
+            let buffer = buffers.get_or_format(sink.style(), &self.shared, &event);
+            is_dropped |= !sink.write(buffer).await;
+        }
+
+        // Counted once even if dropped by several sinks.
+        if is_dropped {
+            let object = event.object.as_deref();
+            self.shared.dropped.add(event.level, object);
+        }
+
         self.shared.pool.clear(event.payload_id);
+    }
+
+    fn prepare_report(&self, dropped: Dropped) -> Option<PreparedEvent> {
+        let payload_id = self.shared.pool.create_with(|payload| {
+            let _ = write!(payload.message, "{} events dropped", dropped.total());
+
+            for (level, count) in &dropped.counts {
+                payload.push(level_name(level), FieldValue::U64(*count));
+            }
+        })?;
+
+        Some(PreparedEvent {
+            timestamp: SystemTime::now(),
+            level: Level::WARN,
+            trace_id: None,
+            object: dropped
+                .group
+                .map(|group| Arc::new(ObjectMeta { group, key: None })),
+            span_id: None,
+            payload_id,
+        })
+    }
+
+    fn update_limiter(&self) {
+        let config = self.ctx.config().rate_limit.clone();
+        self.shared.limiter.configure(config);
+    }
+
+    fn update_ring_buffers(&self) {
+        let config = self.ctx.config().ring_buffer.clone();
+        self.shared.rings.configure(config, &self.shared.pool);
+    }
+
+    fn update_trace_chains(&self) {
+        let config = self.ctx.config().tail_sampling.clone();
+        self.shared.chains.configure(config, &self.shared.pool);
+    }
+
+    fn update_filter(&self) {
+        let config = self.ctx.config();
+
+        let ring_buffer = config.ring_buffer.as_ref().map(|c| c.level.into());
+        let tail_sampling = config.tail_sampling.as_ref().map(|c| c.level.into());
+        let capture = ring_buffer
+            .into_iter()
+            .chain(tail_sampling)
+            .fold(LevelFilter::OFF, LevelFilter::max);
+
+        let filter = Filter::new(&config.filter, self.shared.default_level, capture);
+        self.shared.filter.store(Arc::new(filter));
+
+        // Callsites cache decisions, so they must be reevaluated.
+        tracing::callsite::rebuild_interest_cache();
+    }
+}
+
+fn record_metrics(dropped: &Dropped) {
+    for (level, count) in &dropped.counts {
+        let level = level_name(level);
 
-        // <timestamp> <level> [<trace_id>] <object> - <message>\t<fields>
-
-        T::Timestamp::fmt(out, &event.timestamp);
-        out.push(' ');
-        T::Level::fmt(out, &event.level);
-        out.push_str(" [");
-        T::TraceId::fmt(out, &event.trace_id);
-        out.push_str("] ");
-        T::ObjectMeta::fmt(out, &event.object);
-        out.push_str(" - ");
-        T::Payload::fmt(out, &payload);
-
-        // Add ancestors' fields.
-        let mut span_id = event.span_id;
-        while let Some(data) = span_id.and_then(|span_id| self.shared.spans.get(&span_id)) {
-            span_id = data.parent_id.clone();
-            let payload = self
-                .shared
-                .pool
-                .get(data.payload_id)
-                .expect("unknown string");
-            out.push_str(&payload);
+        if let Some(group) = &dropped.group {
+            let group = group.clone();
+            metrics::counter!("elfo_dropped_events_total", "level" => level, "actor_group" => group)
+                .increment(*count);
+        } else {
+            metrics::counter!("elfo_dropped_events_total", "level" => level).increment(*count);
         }
+    }
+}
 
-        out.push('\n');
+fn level_name(level: &Level) -> &'static str {
+    match *level {
+        Level::ERROR => "error",
+        Level::WARN => "warn",
+        Level::INFO => "info",
+        Level::DEBUG => "debug",
+        Level::TRACE => "trace",
     }
 }
 
-async fn open_file(config: &Config) -> Option<File> {
-    if config.sink == Sink::Stdout {
-        return None;
+async fn open_sinks(config: &Config, buffers: &Buffers) -> Vec<SinkWriter> {
+    let mut sinks = Vec::with_capacity(config.sinks().len());
+    for sink in config.sinks() {
+        let sink = SinkWriter::open(sink.clone()).await;
+
+        if sink.style() == Style::Custom && buffers.custom.is_none() {
+            error!("no custom formatter is registered, the text format is used");
+        }
+
+        sinks.push(sink);
     }
+    sinks
+}
 
-    // TODO: rely on deserialize instead.
-    let path = config
-        .path
-        .as_ref()
-        .expect("the config path must be provided");
+/// Formatted events, one per style.
+struct Buffers {
+    buffers: [String; Style::COUNT],
+    formatted: [bool; Style::COUNT],
+    custom: Option<Arc<dyn EventFormatter>>,
+    timestamp: TimestampConfig,
+}
 
-    let file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(path)
-        .await
-        .expect("cannot open the config file");
+impl Buffers {
+    fn new(custom: Option<Arc<dyn EventFormatter>>, timestamp: TimestampConfig) -> Self {
+        Self {
+            buffers: Default::default(),
+            formatted: Default::default(),
+            custom,
+            timestamp,
+        }
+    }
+
+    fn clear(&mut self) {
+        self.formatted = [false; Style::COUNT];
+    }
+
+    fn get_or_format(&mut self, style: Style, shared: &Shared, event: &PreparedEvent) -> &str {
+        let idx = style as usize;
+        let buffer = &mut self.buffers[idx];
 
-    Some(file)
+        if !self.formatted[idx] {
+            self.formatted[idx] = true;
+            buffer.clear();
+
+            let payload = shared.pool.get(event.payload_id).expect("unknown payload");
+            let view = EventView::new(shared, event, &payload, self.timestamp);
+
+            match (style, &self.custom) {
+                (Style::Plain, _) | (Style::Custom, None) => {
+                    TextFormatter::<PlainTheme>::default().format(buffer, &view)
+                }
+                (Style::Colored, _) => {
+                    TextFormatter::<ColoredTheme>::default().format(buffer, &view)
+                }
+                (Style::Json, _) => JsonFormatter.format(buffer, &view),
+                (Style::Logfmt, _) => LogfmtFormatter.format(buffer, &view),
+                (Style::Custom, Some(custom)) => custom.format(buffer, &view),
+            }
+        }
+
+        buffer
+    }
 }
 
-fn can_use_colors(config: &Config) -> bool {
-    config.sink == Sink::Stdout && atty::is(atty::Stream::Stdout)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use arc_swap::ArcSwap;
+    use dashmap::DashMap;
+    use futures_intrusive::channel::GenericChannel;
+    use sharded_slab::Pool;
+    use tracing::{info, info_span};
+    use tracing_subscriber::{prelude::*, registry::Registry};
+
+    use crate::{
+        config::{LogLevel, RingBufferConfig, TailSamplingConfig},
+        layer::PrintLayer,
+    };
+
+    fn shared(capacity: usize) -> Arc<Shared> {
+        Arc::new(Shared {
+            channel: GenericChannel::with_capacity(capacity),
+            pool: Pool::default(),
+            spans: DashMap::default(),
+            dropped: Default::default(),
+            limiter: Default::default(),
+            rings: Default::default(),
+            chains: Default::default(),
+            formatter: Default::default(),
+            clock: Default::default(),
+            filter: ArcSwap::from_pointee(Filter::new(
+                &Default::default(),
+                LevelFilter::TRACE,
+                LevelFilter::OFF,
+            )),
+            default_level: LevelFilter::TRACE,
+        })
+    }
+
+    fn format(formatter: &dyn EventFormatter) -> String {
+        format_with(formatter, Default::default())
+    }
+
+    fn format_with(formatter: &dyn EventFormatter, timestamp: TimestampConfig) -> String {
+        let shared = shared(16);
+        let subscriber = Registry::default().with(PrintLayer::new(shared.clone()));
+        let mut out = String::new();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = info_span!("span", request = "a\"b");
+            let _guard = span.enter();
+            info!(count = 5u64, delta = -1i64, ratio = 0.5, ok = true, opt = ?Some(1), "hello\nworld");
+
+            let event = shared.channel.try_receive().expect("no event");
+            let payload = shared.pool.get(event.payload_id).expect("unknown payload");
+            let view = EventView::new(&shared, &event, &payload, timestamp);
+            formatter.format(&mut out, &view);
+        });
+
+        out
+    }
+
+    #[test]
+    fn text_format_should_be_human_readable() {
+        assert_eq!(
+            format(&TextFormatter::<PlainTheme>::default()),
+            "2021-05-17 20:20:20.123456789  INFO []  - hello\\nworld\
+             \tcount=5\tdelta=-1\tratio=0.5\tok=true\topt=Some(1)\trequest=a\"b\n"
+        );
+    }
+
+    #[test]
+    fn logfmt_format_should_quote_values() {
+        assert_eq!(
+            format(&LogfmtFormatter),
+            "ts=2021-05-17T20:20:20.123456789Z level=info msg=\"hello\\nworld\" \
+             count=5 delta=-1 ratio=0.5 ok=true opt=Some(1) request=\"a\\\"b\"\n"
+        );
+    }
+
+    #[test]
+    fn json_format_should_keep_types() {
+        assert_eq!(
+            format(&JsonFormatter),
+            "{\"timestamp\":\"2021-05-17T20:20:20.123456789Z\",\"level\":\"INFO\",\
+             \"message\":\"hello\\nworld\",\"fields\":{\"count\":5,\"delta\":-1,\"ratio\":0.5,\
+             \"ok\":true,\"opt\":\"Some(1)\",\"request\":\"a\\\"b\"}}\n"
+        );
+    }
+
+    #[test]
+    fn json_format_should_not_duplicate_keys() {
+        let shared = shared(16);
+        let subscriber = Registry::default().with(PrintLayer::new(shared.clone()));
+        let mut out = String::new();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let outer = info_span!("outer", id = 1u64, outer = true);
+            let inner = info_span!(parent: &outer, "inner", id = 2u64, step = 3u64);
+            let _guard = inner.enter();
+            info!(step = 4u64, "hello");
+
+            let event = shared.channel.try_receive().expect("no event");
+            let payload = shared.pool.get(event.payload_id).expect("unknown payload");
+            let view = EventView::new(&shared, &event, &payload, Default::default());
+            JsonFormatter.format(&mut out, &view);
+        });
+
+        assert!(out.ends_with("\"fields\":{\"step\":4,\"id\":2,\"outer\":true}}\n"));
+    }
+
+    #[test]
+    fn timestamp_options_should_be_applied() {
+        let timestamp = toml::from_str("unix = true\nprecision = \"ms\"").unwrap();
+        let out = format_with(&JsonFormatter, timestamp);
+        assert!(out.starts_with("{\"timestamp\":1621282820.123,\"level\""));
+
+        let timestamp = toml::from_str("precision = \"s\"").unwrap();
+        let out = format_with(&TextFormatter::<PlainTheme>::default(), timestamp);
+        assert!(out.starts_with("2021-05-17 20:20:20  INFO"));
+    }
+
+    #[test]
+    fn custom_formatter_should_get_event_view() {
+        struct Custom;
+
+        impl EventFormatter for Custom {
+            fn format(&self, out: &mut String, event: &EventView<'_>) {
+                let _ = write!(out, "{} {}", event.level(), event.payload().message());
+
+                let spans = event.spans().collect::<Vec<_>>();
+                let fields = Some(event.payload())
+                    .into_iter()
+                    .chain(spans.iter().map(|span| span.payload()))
+                    .flat_map(|payload| payload.fields());
+
+                for field in fields {
+                    if let FieldValue::Str(value) = field.value() {
+                        let _ = write!(out, " {}:{}", field.name(), value);
+                    }
+                }
+            }
+        }
+
+        assert_eq!(
+            format(&Custom),
+            "INFO hello\nworld opt:Some(1) request:a\"b"
+        );
+    }
+
+    #[test]
+    fn max_level_should_follow_config_and_captured_levels() {
+        use tracing::Subscriber;
+        use tracing_subscriber::EnvFilter;
+
+        let shared = shared(16);
+        let subscriber = Registry::default()
+            .with(None::<EnvFilter>)
+            .with(PrintLayer::new(shared.clone()));
+
+        let filter = Filter::new(&Default::default(), LevelFilter::INFO, LevelFilter::OFF);
+        shared.filter.store(Arc::new(filter));
+        assert_eq!(subscriber.max_level_hint(), Some(LevelFilter::INFO));
+
+        let filter = Filter::new(&Default::default(), LevelFilter::INFO, LevelFilter::DEBUG);
+        shared.filter.store(Arc::new(filter));
+        assert_eq!(subscriber.max_level_hint(), Some(LevelFilter::DEBUG));
+    }
+
+    #[test]
+    fn it_counts_dropped_events() {
+        let shared = shared(1);
+        let subscriber = Registry::default().with(PrintLayer::new(shared.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            info!("first");
+            info!("second");
+            tracing::warn!("third");
+        });
+
+        let dropped = shared.dropped.take();
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].group, None);
+        assert_eq!(dropped[0].counts, vec![(Level::WARN, 1), (Level::INFO, 1)]);
+
+        // Only the first event is sent.
+        let event = shared.channel.try_receive().expect("no event");
+        let payload = shared.pool.get(event.payload_id).expect("unknown payload");
+        assert_eq!(payload.message, "first");
+        assert!(shared.channel.try_receive().is_err());
+    }
+
+    #[test]
+    fn it_dumps_ring_buffer_on_failure() {
+        let shared = shared(16);
+        let ring_buffer = RingBufferConfig {
+            capacity: 2,
+            level: LogLevel::Trace,
+        };
+        shared.rings.configure(Some(ring_buffer), &shared.pool);
+        let filter = Filter::new(&Default::default(), LevelFilter::INFO, LevelFilter::TRACE);
+        shared.filter.store(Arc::new(filter));
+
+        let subscriber = Registry::default().with(PrintLayer::new(shared.clone()));
+        let meta = Arc::new(ObjectMeta {
+            group: "group".into(),
+            key: None,
+        });
+
+        tracing::subscriber::with_default(subscriber, || {
+            elfo::tls::sync_scope(meta.clone(), elfo::trace_id::generate(), || {
+                tracing::trace!("evicted");
+                tracing::debug!("first");
+                info!("written");
+                tracing::trace!("second");
+                shared.on_status_changed(&meta, Status::Failed);
+                tracing::error!("status changed");
+            });
+        });
+
+        let mut messages = Vec::new();
+        while let Ok(event) = shared.channel.try_receive() {
+            let payload = shared.pool.get(event.payload_id).expect("unknown payload");
+            messages.push(payload.message.clone());
+        }
+
+        assert_eq!(
+            messages,
+            vec!["written", "first", "second", "status changed"]
+        );
+    }
+
+    #[test]
+    fn kept_events_should_outlive_spans() {
+        let shared = shared(16);
+        let ring_buffer = RingBufferConfig {
+            capacity: 2,
+            level: LogLevel::Debug,
+        };
+        shared.rings.configure(Some(ring_buffer), &shared.pool);
+        let filter = Filter::new(&Default::default(), LevelFilter::INFO, LevelFilter::DEBUG);
+        shared.filter.store(Arc::new(filter));
+
+        let subscriber = Registry::default().with(PrintLayer::new(shared.clone()));
+        let meta = Arc::new(ObjectMeta {
+            group: "group".into(),
+            key: None,
+        });
+
+        tracing::subscriber::with_default(subscriber, || {
+            elfo::tls::sync_scope(meta.clone(), elfo::trace_id::generate(), || {
+                info_span!("outer", request = 1u64).in_scope(|| {
+                    info_span!("inner", attempt = 2u64).in_scope(|| {
+                        tracing::debug!(step = 3u64, "kept");
+                    });
+                });
+
+                // Spans are closed, their ids can be reused.
+                info_span!("other", unrelated = 4u64).in_scope(|| {
+                    shared.on_status_changed(&meta, Status::Failed);
+                });
+            });
+        });
+
+        let event = shared.channel.try_receive().expect("no event");
+        assert!(event.span_id.is_none());
+
+        let mut out = String::new();
+        let payload = shared.pool.get(event.payload_id).expect("unknown payload");
+        let view = EventView::new(&shared, &event, &payload, Default::default());
+        JsonFormatter.format(&mut out, &view);
+        assert!(out.contains("\"fields\":{\"step\":3,\"attempt\":2,\"request\":1}}"));
+    }
+
+    #[test]
+    fn it_writes_trace_chain_on_error() {
+        let shared = shared(16);
+        let tail_sampling = TailSamplingConfig {
+            level: LogLevel::Debug,
+            window: Duration::from_secs(5),
+            capacity: 10,
+        };
+        shared.chains.configure(Some(tail_sampling), &shared.pool);
+        let filter = Filter::new(&Default::default(), LevelFilter::INFO, LevelFilter::DEBUG);
+        shared.filter.store(Arc::new(filter));
+
+        let subscriber = Registry::default().with(PrintLayer::new(shared.clone()));
+        let meta = Arc::new(ObjectMeta {
+            group: "group".into(),
+            key: None,
+        });
+
+        tracing::subscriber::with_default(subscriber, || {
+            let trace_id = elfo::trace_id::generate();
+            elfo::tls::sync_scope(meta.clone(), trace_id, || {
+                tracing::debug!("discarded");
+            });
+
+            let trace_id = elfo::trace_id::generate();
+            elfo::tls::sync_scope(meta, trace_id, || {
+                tracing::trace!("skipped");
+                tracing::debug!("first");
+                info!("written");
+                tracing::debug!("second");
+                tracing::error!("failed");
+            });
+        });
+
+        let mut messages = Vec::new();
+        while let Ok(event) = shared.channel.try_receive() {
+            let payload = shared.pool.get(event.payload_id).expect("unknown payload");
+            messages.push(payload.message.clone());
+        }
+
+        assert_eq!(messages, vec!["written", "first", "second", "failed"]);
+    }
 }
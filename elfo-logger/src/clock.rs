@@ -0,0 +1,65 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A clock for timestamps of events, it's cheaper than `SystemTime::now()`,
+/// because it reads TSC if available. The system time at the base point is
+/// recalibrated periodically, because the system time can be adjusted.
+pub(crate) struct Clock {
+    clock: quanta::Clock,
+    base_raw: u64,
+    /// Nanoseconds since the unix epoch at `base_raw`.
+    base_time: AtomicU64,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        let clock = quanta::Clock::new();
+        let base_raw = clock.raw();
+
+        Self {
+            clock,
+            base_raw,
+            base_time: AtomicU64::new(unix_nanos(SystemTime::now())),
+        }
+    }
+}
+
+impl Clock {
+    pub(crate) fn now(&self) -> SystemTime {
+        let elapsed = self.clock.delta_as_nanos(self.base_raw, self.clock.raw());
+        let base_time = self.base_time.load(Ordering::Relaxed);
+        UNIX_EPOCH + Duration::from_nanos(base_time + elapsed)
+    }
+
+    pub(crate) fn recalibrate(&self) {
+        let elapsed = self.clock.delta_as_nanos(self.base_raw, self.clock.raw());
+        let base_time = unix_nanos(SystemTime::now()).saturating_sub(elapsed);
+        self.base_time.store(base_time, Ordering::Relaxed);
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_follows_system_time() {
+        let clock = Clock::default();
+
+        for _ in 0..2 {
+            let before = SystemTime::now() - Duration::from_millis(10);
+            let now = clock.now();
+            let after = SystemTime::now() + Duration::from_millis(10);
+            assert!(before <= now && now <= after);
+
+            clock.recalibrate();
+        }
+    }
+}
@@ -1,24 +1,383 @@
-use std::path::PathBuf;
+use std::{collections::HashMap, convert::TryFrom, path::PathBuf, time::Duration};
 
+use bytesize::ByteSize;
 use serde::Deserialize;
+use tracing::level_filters::LevelFilter;
 
+/// Fields of the default sink are used only if `sinks` are empty.
+/// ```toml
+/// [system.loggers]
+/// sinks = [
+///     { sink = "Stderr", level = "warn" },
+///     { sink = "File", path = "app.log", format = "json" },
+/// ]
+///
+/// [system.loggers.filter]
+/// default = "info"
+/// groups = { producers = "debug" }
+/// targets = { hyper = "warn" }
+///
+/// [system.loggers.rate_limit]
+/// max_events = 100
+/// window = "1s"
+///
+/// [system.loggers.ring_buffer]
+/// capacity = 100
+/// level = "trace"
+///
+/// [system.loggers.tail_sampling]
+/// level = "debug"
+/// window = "5s"
+///
+/// [system.loggers.timestamp]
+/// timezone = "local"
+/// precision = "ms"
+/// ```
 #[derive(Debug, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub(crate) struct Config {
+    #[serde(flatten)]
+    default_sink: SinkConfig,
+    #[serde(default)]
+    sinks: Vec<SinkConfig>,
+    #[serde(default)]
+    pub(crate) filter: FilterConfig,
+    /// Events are unlimited if unset.
+    pub(crate) rate_limit: Option<RateLimitConfig>,
+    /// Disabled if unset.
+    pub(crate) ring_buffer: Option<RingBufferConfig>,
+    /// Disabled if unset.
+    pub(crate) tail_sampling: Option<TailSamplingConfig>,
+    /// The same for all sinks.
+    #[serde(default)]
+    pub(crate) timestamp: TimestampConfig,
+}
+
+impl Config {
+    pub(crate) fn sinks(&self) -> &[SinkConfig] {
+        if self.sinks.is_empty() {
+            std::slice::from_ref(&self.default_sink)
+        } else {
+            &self.sinks
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "RawSinkConfig")]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub(crate) struct SinkConfig {
     #[serde(default)]
     pub(crate) sink: Sink,
+    /// Required for the `File` and `Unix` sinks.
     pub(crate) path: Option<PathBuf>,
+    #[serde(default)]
+    pub(crate) format: Format,
+    /// Events below the level are skipped, all events are written if unset.
+    pub(crate) level: Option<LogLevel>,
+    /// Only for the `File` sink.
+    pub(crate) rotation: Option<RotationConfig>,
     // TODO: colors
 }
 
-#[derive(Debug, PartialEq, Deserialize)]
+/// `SinkConfig` before validation, the fields must be the same.
+#[derive(Deserialize)]
+struct RawSinkConfig {
+    #[serde(default)]
+    sink: Sink,
+    path: Option<PathBuf>,
+    #[serde(default)]
+    format: Format,
+    level: Option<LogLevel>,
+    rotation: Option<RotationConfig>,
+}
+
+impl TryFrom<RawSinkConfig> for SinkConfig {
+    type Error = String;
+
+    fn try_from(raw: RawSinkConfig) -> Result<Self, Self::Error> {
+        if matches!(raw.sink, Sink::File | Sink::Unix) && raw.path.is_none() {
+            return Err(format!(
+                "`path` must be provided for the {:?} sink",
+                raw.sink
+            ));
+        }
+
+        Ok(Self {
+            sink: raw.sink,
+            path: raw.path,
+            format: raw.format,
+            level: raw.level,
+            rotation: raw.rotation,
+        })
+    }
+}
+
+/// Level directives, applied in addition to `RUST_LOG` if it's set.
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub(crate) struct FilterConfig {
+    /// Used if there is no directive for a group or a target,
+    /// "info" by default.
+    pub(crate) default: Option<LogLevel>,
+    /// Directives for actor groups, e.g. `producers = "debug"`.
+    #[serde(default)]
+    pub(crate) groups: HashMap<String, LogLevel>,
+    /// Directives for targets (usually module paths) including nested ones,
+    /// e.g. `hyper = "warn"`. They take precedence over groups.
+    #[serde(default)]
+    pub(crate) targets: HashMap<String, LogLevel>,
+}
+
+/// Limits events per callsite and actor. Repeats are collapsed into one
+/// event with the `repeated` field, it's written when the window closes.
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub(crate) struct RateLimitConfig {
+    /// How many events are written in the window as is.
+    pub(crate) max_events: u64,
+    /// "1s" by default.
+    #[serde(with = "humantime_serde", default = "default_window")]
+    #[cfg_attr(feature = "schema", schemars(with = "String"))]
+    pub(crate) window: Duration,
+}
+
+fn default_window() -> Duration {
+    Duration::from_secs(1)
+}
+
+/// Recent events of each actor that aren't written because of levels are
+/// kept in memory and written if the actor fails.
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub(crate) struct RingBufferConfig {
+    /// How many events are kept per actor.
+    pub(crate) capacity: usize,
+    /// The most verbose kept level, "trace" by default. Note that `RUST_LOG`
+    /// excludes events before they're kept.
+    #[serde(default = "default_ring_buffer_level")]
+    pub(crate) level: LogLevel,
+}
+
+fn default_ring_buffer_level() -> LogLevel {
+    LogLevel::Trace
+}
+
+/// Events of each trace that aren't written because of levels are kept in
+/// memory during the window. If an event of the trace reaches the `error`
+/// level, the whole chain is written, otherwise it's discarded.
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub(crate) struct TailSamplingConfig {
+    /// The most verbose kept level, "debug" by default. Note that `RUST_LOG`
+    /// excludes events before they're kept.
+    #[serde(default = "default_tail_sampling_level")]
+    pub(crate) level: LogLevel,
+    /// "5s" by default.
+    #[serde(with = "humantime_serde", default = "default_tail_sampling_window")]
+    #[cfg_attr(feature = "schema", schemars(with = "String"))]
+    pub(crate) window: Duration,
+    /// How many events are kept per trace, 1000 by default.
+    #[serde(default = "default_tail_sampling_capacity")]
+    pub(crate) capacity: usize,
+}
+
+fn default_tail_sampling_level() -> LogLevel {
+    LogLevel::Debug
+}
+
+fn default_tail_sampling_window() -> Duration {
+    Duration::from_secs(5)
+}
+
+fn default_tail_sampling_capacity() -> usize {
+    1000
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub(crate) struct TimestampConfig {
+    /// Ignored for unix timestamps.
+    #[serde(default)]
+    pub(crate) timezone: TimeZone,
+    #[serde(default)]
+    pub(crate) precision: Precision,
+    /// Write seconds since the unix epoch instead of RFC 3339.
+    #[serde(default)]
+    pub(crate) unix: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TimeZone {
+    #[default]
+    Utc,
+    Local,
+}
+
+/// Digits of fractional seconds.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Precision {
+    S,
+    Ms,
+    Us,
+    #[default]
+    Ns,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub(crate) enum Sink {
     File,
+    #[default]
     Stdout,
-    // TODO: stdout + stderr
+    Stderr,
+    /// A Unix datagram socket, one event per datagram.
+    Unix,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Off,
+}
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Trace => LevelFilter::TRACE,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Off => LevelFilter::OFF,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Default)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Format {
+    /// Human-readable lines, colored if the sink is a terminal.
+    #[default]
+    Text,
+    /// One JSON object per line.
+    Json,
+    /// `key=value` pairs, values are quoted if needed.
+    Logfmt,
+    /// Formatted by `EventFormatter` registered by
+    /// `elfo_logger::new_with_formatter()` or `init_with_formatter()`.
+    Custom,
+}
+
+/// Rotated files are named `<path>.<timestamp>` (with the `_<n>` suffix if
+/// rotated several times in the same millisecond), e.g.
+/// `app.log.2021-05-17T202020.123Z.gz`.
+#[derive(Debug, Clone, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub(crate) struct RotationConfig {
+    /// Rotate if the file is larger, e.g. "100MB".
+    #[serde(default)]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+    pub(crate) max_size: Option<ByteSize>,
+    /// Rotate if the file was created earlier, e.g. "1d".
+    #[serde(with = "humantime_serde", default)]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+    pub(crate) max_age: Option<Duration>,
+    /// How many rotated files are kept.
+    #[serde(default = "default_keep")]
+    pub(crate) keep: usize,
+    /// Compress rotated files by gzip.
+    #[serde(default)]
+    pub(crate) compress: bool,
+}
+
+fn default_keep() -> usize {
+    10
 }
 
-impl Default for Sink {
-    fn default() -> Self {
-        Sink::Stdout
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_sink_should_be_used_without_sinks() {
+        let config: Config = toml::from_str(
+            r#"
+            sink = "File"
+            path = "app.log"
+            format = "json"
+            rotation = { max_size = "100MB", max_age = "1d" }
+            "#,
+        )
+        .unwrap();
+
+        let sinks = config.sinks();
+        assert_eq!(sinks.len(), 1);
+        assert_eq!(sinks[0].sink, Sink::File);
+        assert_eq!(sinks[0].path, Some(PathBuf::from("app.log")));
+        assert_eq!(sinks[0].format, Format::Json);
+        assert_eq!(sinks[0].level, None);
+
+        let rotation = sinks[0].rotation.as_ref().unwrap();
+        assert_eq!(rotation.max_size, Some(ByteSize::mb(100)));
+        assert_eq!(rotation.max_age, Some(Duration::from_secs(86400)));
+        assert_eq!(rotation.keep, 10);
+        assert!(!rotation.compress);
+    }
+
+    #[test]
+    fn sinks_should_replace_default_one() {
+        let config: Config = toml::from_str(
+            r#"
+            sinks = [
+                { sink = "Stderr", level = "warn" },
+                { sink = "File", path = "app.log", format = "json" },
+            ]
+            "#,
+        )
+        .unwrap();
+
+        let sinks = config.sinks();
+        assert_eq!(sinks.len(), 2);
+        assert_eq!(sinks[0].sink, Sink::Stderr);
+        assert_eq!(sinks[0].format, Format::Text);
+        assert_eq!(sinks[0].level, Some(LogLevel::Warn));
+        assert_eq!(sinks[1].sink, Sink::File);
+        assert_eq!(sinks[1].format, Format::Json);
+    }
+
+    #[test]
+    fn path_should_be_required_for_files_and_sockets() {
+        for sink in &["File", "Unix"] {
+            let err = toml::from_str::<Config>(&format!("sink = {:?}", sink)).unwrap_err();
+            assert!(err.to_string().contains("`path` must be provided"));
+
+            let raw = format!("sinks = [{{ sink = {:?} }}]", sink);
+            assert!(toml::from_str::<Config>(&raw).is_err());
+        }
+
+        assert!(toml::from_str::<Config>("sink = \"Stderr\"").is_ok());
+    }
+
+    #[test]
+    fn rate_limit_should_be_optional() {
+        let config: Config = toml::from_str("").unwrap();
+        assert!(config.rate_limit.is_none());
+
+        let config: Config = toml::from_str("rate_limit = { max_events = 10 }").unwrap();
+        let rate_limit = config.rate_limit.unwrap();
+        assert_eq!(rate_limit.max_events, 10);
+        assert_eq!(rate_limit.window, Duration::from_secs(1));
     }
 }
@@ -0,0 +1,148 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use dashmap::DashMap;
+use fxhash::FxBuildHasher;
+use tracing::Level;
+
+use elfo_core::_priv::ObjectMeta;
+
+const LEVELS: [Level; 5] = [
+    Level::ERROR,
+    Level::WARN,
+    Level::INFO,
+    Level::DEBUG,
+    Level::TRACE,
+];
+
+/// Counts events that are dropped because the channel or the pool is full
+/// or a sink cannot accept them, e.g. a full unix socket.
+#[derive(Default)]
+pub(crate) struct DroppedEvents {
+    /// Events outside actors.
+    common: Counters,
+    groups: DashMap<String, Counters, FxBuildHasher>,
+}
+
+/// Dropped events of one group since the last report.
+pub(crate) struct Dropped {
+    pub(crate) group: Option<String>,
+    /// Only levels with dropped events, from `ERROR` to `TRACE`.
+    pub(crate) counts: Vec<(Level, u64)>,
+}
+
+impl Dropped {
+    pub(crate) fn total(&self) -> u64 {
+        self.counts.iter().map(|(_, count)| count).sum()
+    }
+}
+
+impl DroppedEvents {
+    pub(crate) fn add(&self, level: Level, object: Option<&ObjectMeta>) {
+        self.add_many(level, object, 1);
+    }
+
+    pub(crate) fn add_many(&self, level: Level, object: Option<&ObjectMeta>, count: u64) {
+        let object = match object {
+            Some(object) => object,
+            None => return self.common.add(level, count),
+        };
+
+        // Avoid allocations in the common case.
+        if let Some(counters) = self.groups.get(&object.group) {
+            counters.add(level, count);
+        } else {
+            self.groups
+                .entry(object.group.clone())
+                .or_default()
+                .add(level, count);
+        }
+    }
+
+    /// Returns dropped events since the previous call.
+    pub(crate) fn take(&self) -> Vec<Dropped> {
+        let mut dropped = Vec::new();
+
+        if let Some(counts) = self.common.take() {
+            dropped.push(Dropped {
+                group: None,
+                counts,
+            });
+        }
+
+        for item in self.groups.iter() {
+            if let Some(counts) = item.value().take() {
+                dropped.push(Dropped {
+                    group: Some(item.key().clone()),
+                    counts,
+                });
+            }
+        }
+
+        dropped
+    }
+}
+
+#[derive(Default)]
+struct Counters([AtomicU64; LEVELS.len()]);
+
+impl Counters {
+    fn add(&self, level: Level, count: u64) {
+        let idx = LEVELS
+            .iter()
+            .position(|l| *l == level)
+            .expect("unknown level");
+        self.0[idx].fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn take(&self) -> Option<Vec<(Level, u64)>> {
+        let counts = LEVELS
+            .iter()
+            .zip(self.0.iter())
+            .map(|(level, counter)| (*level, counter.swap(0, Ordering::Relaxed)))
+            .filter(|(_, count)| *count > 0)
+            .collect::<Vec<_>>();
+
+        if counts.is_empty() {
+            None
+        } else {
+            Some(counts)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn meta(group: &str) -> ObjectMeta {
+        ObjectMeta {
+            group: group.into(),
+            key: None,
+        }
+    }
+
+    #[test]
+    fn it_counts_per_group_and_level() {
+        let dropped = DroppedEvents::default();
+        assert!(dropped.take().is_empty());
+
+        dropped.add(Level::INFO, None);
+        dropped.add(Level::WARN, Some(&meta("producers")));
+        dropped.add(Level::INFO, Some(&meta("producers")));
+        dropped.add(Level::WARN, Some(&meta("producers")));
+
+        let mut items = dropped.take();
+        items.sort_by(|a, b| a.group.cmp(&b.group));
+        assert_eq!(items.len(), 2);
+
+        assert_eq!(items[0].group, None);
+        assert_eq!(items[0].counts, vec![(Level::INFO, 1)]);
+
+        assert_eq!(items[1].group.as_deref(), Some("producers"));
+        assert_eq!(items[1].counts, vec![(Level::WARN, 2), (Level::INFO, 1)]);
+        assert_eq!(items[1].total(), 3);
+
+        // Counters are reset.
+        assert!(dropped.take().is_empty());
+    }
+}
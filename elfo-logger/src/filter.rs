@@ -0,0 +1,197 @@
+use std::cmp::Reverse;
+
+use fxhash::FxHashMap;
+use tracing::{level_filters::LevelFilter, subscriber::Interest, Metadata};
+
+use elfo_core::tls;
+
+use crate::config::FilterConfig;
+
+/// Level directives from the config, replaced on `ConfigUpdated`.
+///
+/// Directives for targets are more specific than ones for groups, so they
+/// take precedence. Targets are known at callsites, but groups are known
+/// only when an event happens, so group directives are checked per event.
+///
+/// Events up to the `capture` level are enabled even if they aren't written,
+/// because they are kept in memory (e.g. in ring buffers of actors).
+pub(crate) struct Filter {
+    default: LevelFilter,
+    capture: LevelFilter,
+    groups: FxHashMap<String, LevelFilter>,
+    /// Sorted from the longest prefix to the shortest one.
+    targets: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    pub(crate) fn new(config: &FilterConfig, default: LevelFilter, capture: LevelFilter) -> Self {
+        let groups = config
+            .groups
+            .iter()
+            .map(|(group, level)| (group.clone(), LevelFilter::from(*level)))
+            .collect();
+
+        let mut targets = config
+            .targets
+            .iter()
+            .map(|(target, level)| (target.clone(), LevelFilter::from(*level)))
+            .collect::<Vec<_>>();
+
+        targets.sort_by_key(|(target, _)| Reverse(target.len()));
+
+        Self {
+            default: config.default.map_or(default, LevelFilter::from),
+            capture,
+            groups,
+            targets,
+        }
+    }
+
+    /// The most verbose level among all directives.
+    pub(crate) fn max_level(&self) -> LevelFilter {
+        let groups = self.groups.values();
+        let targets = self.targets.iter().map(|(_, level)| level);
+        groups
+            .chain(targets)
+            .copied()
+            .fold(self.default.max(self.capture), LevelFilter::max)
+    }
+
+    pub(crate) fn interest(&self, meta: &Metadata<'_>) -> Interest {
+        let level = *meta.level();
+
+        if level <= self.capture {
+            return Interest::always();
+        }
+
+        let enabled = match self.target_level(meta.target()) {
+            Some(threshold) => level <= threshold,
+            None => {
+                let enabled = level <= self.default;
+
+                // The decision depends on the current group.
+                if self.groups.values().any(|t| (level <= *t) != enabled) {
+                    return Interest::sometimes();
+                }
+
+                enabled
+            }
+        };
+
+        if enabled {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    pub(crate) fn enabled(&self, meta: &Metadata<'_>) -> bool {
+        *meta.level() <= self.capture || self.is_writable(meta)
+    }
+
+    /// Expects an enabled event, so only captured levels need to be checked.
+    pub(crate) fn is_written(&self, meta: &Metadata<'_>) -> bool {
+        if *meta.level() > self.capture {
+            return true;
+        }
+
+        self.is_writable(meta)
+    }
+
+    fn is_writable(&self, meta: &Metadata<'_>) -> bool {
+        let threshold = if self.groups.is_empty() {
+            self.threshold(meta.target(), None)
+        } else {
+            let object = tls::try_meta();
+            let group = object.as_ref().map(|object| object.group.as_str());
+            self.threshold(meta.target(), group)
+        };
+
+        *meta.level() <= threshold
+    }
+
+    fn threshold(&self, target: &str, group: Option<&str>) -> LevelFilter {
+        self.target_level(target)
+            .or_else(|| group.and_then(|group| self.groups.get(group).copied()))
+            .unwrap_or(self.default)
+    }
+
+    fn target_level(&self, target: &str) -> Option<LevelFilter> {
+        self.targets
+            .iter()
+            .find(|(prefix, _)| is_nested(target, prefix))
+            .map(|(_, level)| *level)
+    }
+}
+
+/// Checks that `target` is `prefix` or its submodule.
+fn is_nested(target: &str, prefix: &str) -> bool {
+    target.starts_with(prefix)
+        && (target.len() == prefix.len() || target[prefix.len()..].starts_with("::"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(config: &str) -> Filter {
+        let config: FilterConfig = toml::from_str(config).unwrap();
+        Filter::new(&config, LevelFilter::INFO, LevelFilter::OFF)
+    }
+
+    #[test]
+    fn it_uses_default_level() {
+        let filter = filter("");
+        assert_eq!(filter.threshold("app", None), LevelFilter::INFO);
+        assert_eq!(filter.max_level(), LevelFilter::INFO);
+
+        let filter = self::filter(r#"default = "warn""#);
+        assert_eq!(
+            filter.threshold("app", Some("producers")),
+            LevelFilter::WARN
+        );
+        assert_eq!(filter.max_level(), LevelFilter::WARN);
+    }
+
+    #[test]
+    fn it_prefers_targets_to_groups() {
+        let filter = filter(
+            r#"
+            groups = { producers = "debug", consumers = "off" }
+            targets = { hyper = "warn", "hyper::client" = "error", app = "trace" }
+            "#,
+        );
+
+        assert_eq!(filter.threshold("app", None), LevelFilter::TRACE);
+        assert_eq!(
+            filter.threshold("app::db", Some("consumers")),
+            LevelFilter::TRACE
+        );
+        assert_eq!(filter.threshold("apps", None), LevelFilter::INFO);
+        assert_eq!(
+            filter.threshold("apps", Some("producers")),
+            LevelFilter::DEBUG
+        );
+        assert_eq!(
+            filter.threshold("apps", Some("consumers")),
+            LevelFilter::OFF
+        );
+        assert_eq!(
+            filter.threshold("hyper", Some("producers")),
+            LevelFilter::WARN
+        );
+        assert_eq!(
+            filter.threshold("hyper::client::pool", None),
+            LevelFilter::ERROR
+        );
+        assert_eq!(filter.max_level(), LevelFilter::TRACE);
+    }
+
+    #[test]
+    fn captured_levels_should_be_enabled() {
+        let config = toml::from_str("").unwrap();
+        let filter = Filter::new(&config, LevelFilter::INFO, LevelFilter::DEBUG);
+        assert_eq!(filter.threshold("app", None), LevelFilter::INFO);
+        assert_eq!(filter.max_level(), LevelFilter::DEBUG);
+    }
+}
@@ -0,0 +1,272 @@
+use std::{fmt::Write, marker::PhantomData};
+
+use sharded_slab::pool::Ref;
+use tracing::{span::Id as SpanId, Level};
+
+use elfo_core::trace_id::TraceId;
+
+use crate::{
+    config::TimestampConfig,
+    formatters::{
+        Formatter, JsonLevel, JsonString, JsonValue, LogfmtLevel, LogfmtString, LogfmtValue,
+        QuotedLogfmtString, Rfc3339,
+    },
+    payload::Payload,
+    theme::{PlainTheme, Theme},
+    timestamp::Timestamp,
+    PreparedEvent, Shared,
+};
+
+/// Formats events for sinks with `format = "custom"`.
+/// Registered by `elfo_logger::new_with_formatter()` or `init_with_formatter()`.
+pub trait EventFormatter: Send + Sync + 'static {
+    /// Appends the event to `out` including the trailing newline.
+    fn format(&self, out: &mut String, event: &EventView<'_>);
+}
+
+/// An event passed to formatters.
+pub struct EventView<'a> {
+    shared: &'a Shared,
+    event: &'a PreparedEvent,
+    payload: &'a Payload,
+    timestamp: TimestampConfig,
+}
+
+impl<'a> EventView<'a> {
+    pub(crate) fn new(
+        shared: &'a Shared,
+        event: &'a PreparedEvent,
+        payload: &'a Payload,
+        timestamp: TimestampConfig,
+    ) -> Self {
+        Self {
+            shared,
+            event,
+            payload,
+            timestamp,
+        }
+    }
+
+    /// Formatted according to the `timestamp` section of the config.
+    pub fn timestamp(&self) -> Timestamp {
+        Timestamp::new(self.event.timestamp, self.timestamp)
+    }
+
+    pub fn level(&self) -> Level {
+        self.event.level
+    }
+
+    pub fn trace_id(&self) -> Option<TraceId> {
+        self.event.trace_id
+    }
+
+    /// The group of the actor that has emitted the event.
+    pub fn group(&self) -> Option<&str> {
+        self.event.object.as_ref().map(|object| &object.group[..])
+    }
+
+    /// The key of the actor that has emitted the event, if the group is keyed.
+    pub fn key(&self) -> Option<&str> {
+        self.event.object.as_ref()?.key.as_deref()
+    }
+
+    /// The message and fields of the event itself.
+    pub fn payload(&self) -> &Payload {
+        self.payload
+    }
+
+    /// Spans of the event, from the innermost one to the root.
+    ///
+    /// Events kept in memory before writing (e.g. in ring buffers) have no
+    /// spans, fields of their spans are appended to their payloads instead.
+    pub fn spans(&self) -> Spans<'a> {
+        Spans {
+            shared: self.shared,
+            next: self.event.span_id.clone(),
+        }
+    }
+}
+
+/// Returned by `EventView::spans()`.
+pub struct Spans<'a> {
+    shared: &'a Shared,
+    next: Option<SpanId>,
+}
+
+impl<'a> Iterator for Spans<'a> {
+    type Item = SpanView<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let span_id = self.next.take()?;
+        let data = self.shared.spans.get(&span_id)?;
+        self.next = data.parent_id.clone();
+        let payload = self.shared.pool.get(data.payload_id);
+        Some(SpanView {
+            payload: payload.expect("unknown payload"),
+        })
+    }
+}
+
+pub struct SpanView<'a> {
+    payload: Ref<'a, Payload>,
+}
+
+impl SpanView<'_> {
+    /// Fields of the span, spans have no messages.
+    pub fn payload(&self) -> &Payload {
+        &self.payload
+    }
+}
+
+/// Human-readable lines, used by `format = "text"`.
+pub struct TextFormatter<T = PlainTheme>(PhantomData<fn() -> T>);
+
+impl<T> Default for TextFormatter<T> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<T: Theme + 'static> EventFormatter for TextFormatter<T> {
+    fn format(&self, out: &mut String, event: &EventView<'_>) {
+        // <timestamp> <level> [<trace_id>] <object> - <message>\t<fields>
+
+        T::Timestamp::fmt(out, &event.timestamp());
+        out.push(' ');
+        T::Level::fmt(out, &event.level());
+        out.push_str(" [");
+        T::TraceId::fmt(out, &event.trace_id());
+        out.push_str("] ");
+        T::ObjectMeta::fmt(out, &event.event.object);
+        out.push_str(" - ");
+        T::Payload::fmt(out, event.payload());
+
+        // Add ancestors' fields.
+        for span in event.spans() {
+            T::Payload::fmt(out, span.payload());
+        }
+
+        out.push('\n');
+    }
+}
+
+/// One JSON object per line, used by `format = "json"`.
+#[derive(Default)]
+pub struct JsonFormatter;
+
+impl EventFormatter for JsonFormatter {
+    fn format(&self, out: &mut String, event: &EventView<'_>) {
+        // {"timestamp":..,"level":..,"trace_id":"..","actor_group":..,"actor_key":..,
+        //  "message":..,"fields":{<fields>,<ancestors' fields>}}
+
+        let timestamp = event.timestamp();
+        if timestamp.is_unix() {
+            let _ = write!(out, "{{\"timestamp\":{}", timestamp);
+        } else {
+            out.push_str("{\"timestamp\":\"");
+            Rfc3339::fmt(out, &timestamp);
+            out.push('"');
+        }
+        out.push_str(",\"level\":");
+        JsonLevel::fmt(out, &event.level());
+
+        // Trace ids can exceed 2^53, so they're written as strings to be
+        // read by JSON parsers without losing precision.
+        if let Some(trace_id) = event.trace_id() {
+            let _ = write!(out, ",\"trace_id\":\"{}\"", trace_id);
+        }
+
+        if let Some(group) = event.group() {
+            out.push_str(",\"actor_group\":");
+            JsonString::fmt(out, group);
+        }
+
+        if let Some(key) = event.key() {
+            out.push_str(",\"actor_key\":");
+            JsonString::fmt(out, key);
+        }
+
+        out.push_str(",\"message\":");
+        JsonString::fmt(out, event.payload().message());
+
+        // Fields are nested in order to never clash with the keys above.
+        // Keys must be unique, so event fields win over spans' ones and inner
+        // spans win over outer ones.
+        out.push_str(",\"fields\":{");
+        let mut written = Vec::new();
+        let mut push_fields = |payload: &Payload| {
+            for field in payload.fields() {
+                if written.contains(&field.name) {
+                    continue;
+                }
+                if !written.is_empty() {
+                    out.push(',');
+                }
+                written.push(field.name.clone());
+                JsonString::fmt(out, field.name());
+                out.push(':');
+                JsonValue::fmt(out, field.value());
+            }
+        };
+
+        push_fields(event.payload());
+
+        // Add ancestors' fields.
+        for span in event.spans() {
+            push_fields(span.payload());
+        }
+
+        out.push_str("}}\n");
+    }
+}
+
+/// `key=value` pairs, used by `format = "logfmt"`.
+#[derive(Default)]
+pub struct LogfmtFormatter;
+
+impl EventFormatter for LogfmtFormatter {
+    fn format(&self, out: &mut String, event: &EventView<'_>) {
+        // ts=<timestamp> level=<level> trace_id=<trace_id> actor_group=<group>
+        // actor_key=<key> msg="<message>" <fields> <ancestors' fields>
+
+        out.push_str("ts=");
+        Rfc3339::fmt(out, &event.timestamp());
+        out.push_str(" level=");
+        LogfmtLevel::fmt(out, &event.level());
+
+        if let Some(trace_id) = event.trace_id() {
+            let _ = write!(out, " trace_id={}", trace_id);
+        }
+
+        if let Some(group) = event.group() {
+            out.push_str(" actor_group=");
+            LogfmtString::fmt(out, group);
+        }
+
+        if let Some(key) = event.key() {
+            out.push_str(" actor_key=");
+            LogfmtString::fmt(out, key);
+        }
+
+        out.push_str(" msg=");
+        QuotedLogfmtString::fmt(out, event.payload().message());
+
+        let mut push_fields = |payload: &Payload| {
+            for field in payload.fields() {
+                out.push(' ');
+                out.push_str(field.name());
+                out.push('=');
+                LogfmtValue::fmt(out, field.value());
+            }
+        };
+
+        push_fields(event.payload());
+
+        // Add ancestors' fields.
+        for span in event.spans() {
+            push_fields(span.payload());
+        }
+
+        out.push('\n');
+    }
+}
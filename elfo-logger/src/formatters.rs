@@ -1,25 +1,27 @@
-use std::{fmt::Write, hash::Hash, marker::PhantomData, sync::Arc, time::SystemTime};
+use std::{fmt::Write, hash::Hash, marker::PhantomData, sync::Arc};
 
 use tracing::Level;
 
 use elfo_core::{_priv::ObjectMeta, trace_id::TraceId};
 
-pub(crate) trait Formatter<T: ?Sized> {
+use crate::{
+    payload::{self, FieldValue},
+    timestamp::Timestamp,
+};
+
+/// Formats a part of an event, used by themes.
+pub trait Formatter<T: ?Sized> {
     fn fmt(dest: &mut String, v: &T);
 }
 
 // Rfc3339Weak
 
-pub(crate) struct Rfc3339Weak;
+/// Like RFC 3339, but with a space instead of "T" and without the time zone.
+pub struct Rfc3339Weak;
 
-impl Formatter<SystemTime> for Rfc3339Weak {
-    fn fmt(out: &mut String, v: &SystemTime) {
-        let t_idx = out.len() + 10;
-        let _ = write!(out, "{}", humantime::format_rfc3339_nanos(*v));
-        // Replace "T" with " ".
-        out.replace_range(t_idx..t_idx + 1, " ");
-        // Remove trailing "Z".
-        out.pop();
+impl Formatter<Timestamp> for Rfc3339Weak {
+    fn fmt(out: &mut String, v: &Timestamp) {
+        v.write_weak(out);
     }
 }
 
@@ -39,7 +41,7 @@ impl Formatter<Level> for Level {
 
 // ColoredLevel
 
-pub(crate) struct ColoredLevel;
+pub struct ColoredLevel;
 
 impl Formatter<Level> for ColoredLevel {
     fn fmt(out: &mut String, v: &Level) {
@@ -75,54 +77,208 @@ impl Formatter<Arc<ObjectMeta>> for Arc<ObjectMeta> {
     }
 }
 
-// Payload
+// PlainPayload
 
-pub(crate) struct Payload;
+pub struct PlainPayload;
 
-impl Formatter<str> for Payload {
-    fn fmt(out: &mut String, v: &str) {
-        // TODO: escape \t.
-        for (idx, chunk) in v.split('\n').enumerate() {
-            if idx > 0 {
-                out.push_str("\\n");
-            }
+impl Formatter<payload::Payload> for PlainPayload {
+    fn fmt(out: &mut String, v: &payload::Payload) {
+        push_escaped(out, &v.message);
 
-            out.push_str(chunk);
+        // <message>\t<key>=<value>\t<key>=<value>
+        for field in &v.fields {
+            out.push('\t');
+            out.push_str(&field.name);
+            out.push('=');
+            push_value(out, &field.value);
         }
     }
 }
 
 // ColoredPayload
 
-pub(crate) struct ColoredPayload;
+pub struct ColoredPayload;
+
+impl Formatter<payload::Payload> for ColoredPayload {
+    fn fmt(out: &mut String, v: &payload::Payload) {
+        push_escaped(out, &v.message);
+
+        for field in &v.fields {
+            out.push_str("\t\x1b[1m");
+            out.push_str(&field.name);
+            out.push_str("\x1b[22m=");
+            push_value(out, &field.value);
+        }
+    }
+}
+
+fn push_value(out: &mut String, v: &FieldValue) {
+    let _ = match v {
+        FieldValue::I64(v) => write!(out, "{}", v),
+        FieldValue::U64(v) => write!(out, "{}", v),
+        FieldValue::F64(v) => write!(out, "{}", v),
+        FieldValue::Bool(v) => write!(out, "{}", v),
+        FieldValue::Str(v) => return push_escaped(out, v),
+    };
+}
+
+fn push_escaped(out: &mut String, v: &str) {
+    // TODO: escape \t.
+    for (idx, chunk) in v.split('\n').enumerate() {
+        if idx > 0 {
+            out.push_str("\\n");
+        }
+
+        out.push_str(chunk);
+    }
+}
+
+// JsonString
+
+pub(crate) struct JsonString;
 
-impl Formatter<str> for ColoredPayload {
+impl Formatter<str> for JsonString {
     fn fmt(out: &mut String, v: &str) {
-        // TODO: escape \t.
-        for (idx, chunk) in v.split('\n').enumerate() {
-            if idx > 0 {
-                out.push_str("\\n");
+        out.push('"');
+
+        for ch in v.chars() {
+            match ch {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                ch if ch.is_control() => {
+                    let _ = write!(out, "\\u{:04x}", ch as u32);
+                }
+                ch => out.push(ch),
             }
+        }
 
-            // <message>\t<key>=<value>\t<key>=<value>
-            for section in chunk.split('\t') {
-                if let Some((key, value)) = section.split_once('=') {
-                    out.push_str("\t\x1b[1m");
-                    out.push_str(key);
-                    out.push_str("\x1b[22m=");
-                    out.push_str(value);
-                } else {
-                    // It's the message section.
-                    out.push_str(section);
-                }
+        out.push('"');
+    }
+}
+
+// JsonValue
+
+pub(crate) struct JsonValue;
+
+impl Formatter<FieldValue> for JsonValue {
+    fn fmt(out: &mut String, v: &FieldValue) {
+        let _ = match v {
+            FieldValue::I64(v) => write!(out, "{}", v),
+            FieldValue::U64(v) => write!(out, "{}", v),
+            FieldValue::F64(v) if v.is_finite() => write!(out, "{}", v),
+            // JSON doesn't support NaN and infinities.
+            FieldValue::F64(_) => return out.push_str("null"),
+            FieldValue::Bool(v) => write!(out, "{}", v),
+            FieldValue::Str(v) => return JsonString::fmt(out, v),
+        };
+    }
+}
+
+// JsonLevel
+
+pub(crate) struct JsonLevel;
+
+impl Formatter<Level> for JsonLevel {
+    fn fmt(out: &mut String, v: &Level) {
+        out.push_str(match *v {
+            Level::TRACE => "\"TRACE\"",
+            Level::DEBUG => "\"DEBUG\"",
+            Level::INFO => "\"INFO\"",
+            Level::WARN => "\"WARN\"",
+            Level::ERROR => "\"ERROR\"",
+        })
+    }
+}
+
+// LogfmtString
+
+pub(crate) struct LogfmtString;
+
+impl Formatter<str> for LogfmtString {
+    fn fmt(out: &mut String, v: &str) {
+        let need_quotes = v.is_empty()
+            || v.chars()
+                .any(|ch| ch <= ' ' || ch == '=' || ch == '"' || ch == '\\');
+
+        if need_quotes {
+            QuotedLogfmtString::fmt(out, v);
+        } else {
+            out.push_str(v);
+        }
+    }
+}
+
+// QuotedLogfmtString
+
+pub(crate) struct QuotedLogfmtString;
+
+impl Formatter<str> for QuotedLogfmtString {
+    fn fmt(out: &mut String, v: &str) {
+        out.push('"');
+
+        for ch in v.chars() {
+            match ch {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                ch => out.push(ch),
             }
         }
+
+        out.push('"');
+    }
+}
+
+// LogfmtValue
+
+pub(crate) struct LogfmtValue;
+
+impl Formatter<FieldValue> for LogfmtValue {
+    fn fmt(out: &mut String, v: &FieldValue) {
+        let _ = match v {
+            FieldValue::I64(v) => write!(out, "{}", v),
+            FieldValue::U64(v) => write!(out, "{}", v),
+            FieldValue::F64(v) => write!(out, "{}", v),
+            FieldValue::Bool(v) => write!(out, "{}", v),
+            FieldValue::Str(v) => return LogfmtString::fmt(out, v),
+        };
+    }
+}
+
+// LogfmtLevel
+
+pub(crate) struct LogfmtLevel;
+
+impl Formatter<Level> for LogfmtLevel {
+    fn fmt(out: &mut String, v: &Level) {
+        out.push_str(match *v {
+            Level::TRACE => "trace",
+            Level::DEBUG => "debug",
+            Level::INFO => "info",
+            Level::WARN => "warn",
+            Level::ERROR => "error",
+        })
+    }
+}
+
+// Rfc3339
+
+pub(crate) struct Rfc3339;
+
+impl Formatter<Timestamp> for Rfc3339 {
+    fn fmt(out: &mut String, v: &Timestamp) {
+        let _ = write!(out, "{}", v);
     }
 }
 
 // EmptyIfNone
 
-pub(crate) struct EmptyIfNone<I>(PhantomData<I>);
+pub struct EmptyIfNone<I>(PhantomData<I>);
 
 impl<T, I: Formatter<T>> Formatter<Option<T>> for EmptyIfNone<I> {
     fn fmt(out: &mut String, v: &Option<T>) {
@@ -136,7 +292,7 @@ impl<T, I: Formatter<T>> Formatter<Option<T>> for EmptyIfNone<I> {
 
 /// Makes a color based on the fx hash of the value.
 /// Generated colors have constant brightness.
-pub(crate) struct ColoredByHash<I>(PhantomData<I>);
+pub struct ColoredByHash<I>(PhantomData<I>);
 
 impl<T: Hash, I: Formatter<T>> Formatter<T> for ColoredByHash<I> {
     #[allow(clippy::many_single_char_names)]
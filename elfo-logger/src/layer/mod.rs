@@ -1,12 +1,17 @@
-use std::{sync::Arc, time::SystemTime};
-
-use tracing::{span, Event, Subscriber};
+use std::{
+    sync::Arc,
+    time::{Instant, SystemTime},
+};
+
+use tracing::{
+    level_filters::LevelFilter, span, subscriber::Interest, Event, Level, Metadata, Subscriber,
+};
 use tracing_subscriber::layer::{Context, Layer};
 
 use elfo_core::tls;
 
 use self::visitor::Visitor;
-use crate::{PreparedEvent, Shared, SpanData, StringId};
+use crate::{limiter::Verdict, PayloadId, PreparedEvent, Shared, SpanData};
 
 mod visitor;
 
@@ -23,26 +28,119 @@ impl PrintLayer {
         &self,
         simplify_message: bool,
         f: impl FnOnce(&mut Visitor<'_>),
-    ) -> Option<StringId> {
+    ) -> Option<PayloadId> {
         self.shared.pool.create_with(|payload| {
-            let mut visitor = Visitor::new(&self.shared, payload, simplify_message);
+            let mut visitor = Visitor::new(payload, simplify_message);
             f(&mut visitor);
         })
     }
-}
 
-// TODO: log if the pool is full.
+    /// Prepares the payload of an event kept in memory (in ring buffers, trace
+    /// chains or the rate limiter) with fields of its spans, because spans
+    /// can be closed and their ids reused before the event is written.
+    fn prepare_detached(&self, event: &Event<'_>, span_id: Option<&span::Id>) -> Option<PayloadId> {
+        self.prepare(true, |visitor| {
+            event.record(visitor);
+
+            // From the innermost span to the root, like `EventView::spans()`.
+            let mut next = span_id.cloned();
+            while let Some(span_id) = next.take() {
+                let data = ward!(self.shared.spans.get(&span_id), break);
+                if let Some(payload) = self.shared.pool.get(data.payload_id) {
+                    visitor.extend_from(&payload);
+                }
+                next = data.parent_id.clone();
+            }
+        })
+    }
+
+    /// Keeps an event that isn't written in a ring buffer and a trace chain.
+    fn capture(&self, event: PreparedEvent) {
+        let pool = &self.shared.pool;
+        let to_ring = event.object.is_some() && self.shared.rings.accepts(event.level);
+        let to_chain = event.trace_id.is_some() && self.shared.chains.accepts(event.level);
+
+        if to_ring && to_chain {
+            if let Some(copy) = self.copy(&event) {
+                self.shared.chains.push(copy, Instant::now(), pool);
+            }
+        }
+
+        if to_ring {
+            self.shared.rings.push(event, pool);
+        } else if to_chain {
+            self.shared.chains.push(event, Instant::now(), pool);
+        } else {
+            pool.clear(event.payload_id);
+        }
+    }
+
+    fn copy(&self, event: &PreparedEvent) -> Option<PreparedEvent> {
+        let pool = &self.shared.pool;
+        let original = pool.get(event.payload_id)?;
+        let payload_id = pool.create_with(|payload| {
+            payload.message.push_str(&original.message);
+            payload.extend_from(&original);
+        })?;
+
+        Some(PreparedEvent {
+            timestamp: event.timestamp,
+            level: event.level,
+            trace_id: event.trace_id,
+            object: event.object.clone(),
+            span_id: event.span_id.clone(),
+            payload_id,
+        })
+    }
+
+    #[cfg(not(test))]
+    fn now(&self) -> SystemTime {
+        self.shared.clock.now()
+    }
+
+    #[cfg(test)]
+    fn now(&self) -> SystemTime {
+        humantime::parse_rfc3339("2021-05-17T20:20:20.123456789Z").unwrap()
+    }
+
+    fn send(&self, event: PreparedEvent) {
+        self.shared.send(event);
+    }
+
+    /// Counts fields of a span lost because of the full pool.
+    fn drop_span(&self, level: Level) {
+        let object = tls::try_meta();
+        self.shared.dropped.add(level, object.as_deref());
+    }
+}
 
 impl<S: Subscriber> Layer<S> for PrintLayer {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        self.shared.filter.load().interest(metadata)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _: Context<'_, S>) -> bool {
+        self.shared.filter.load().enabled(metadata)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.shared.filter.load().max_level())
+    }
+
     fn new_span(&self, attrs: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let level = *attrs.metadata().level();
         let parent_id = if attrs.is_root() {
             None
         } else {
             let current_span = ctx.current_span();
             attrs.parent().or_else(|| current_span.id()).cloned()
         };
-        let payload_id = ward!(self.prepare(false, |visitor| attrs.record(visitor)));
-        let span = SpanData::new(parent_id, payload_id);
+        let payload_id = match self.prepare(false, |visitor| attrs.record(visitor)) {
+            Some(payload_id) => payload_id,
+            // Events inside the span are written without its fields.
+            None => return self.drop_span(level),
+        };
+        let span = SpanData::new(level, parent_id, payload_id);
         self.shared.spans.insert(id.clone(), span);
     }
 
@@ -51,30 +149,98 @@ impl<S: Subscriber> Layer<S> for PrintLayer {
         let old_payload_id = data.payload_id;
         let old_payload = ward!(self.shared.pool.get(old_payload_id));
 
-        let payload_id = ward!(self.prepare(false, |visitor| {
-            visitor.push(&old_payload);
+        let payload_id = match self.prepare(false, |visitor| {
+            visitor.extend_from(&old_payload);
             record.record(visitor);
-        }));
+        }) {
+            Some(payload_id) => payload_id,
+            // The span keeps old fields.
+            None => return self.drop_span(data.level),
+        };
 
         self.shared.pool.clear(old_payload_id);
         data.payload_id = payload_id;
     }
 
     fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let level = *event.metadata().level();
+        let object = tls::try_meta();
+        let trace_id = tls::try_trace_id();
         let current_span = ctx.current_span();
-        let payload_id = ward!(self.prepare(true, |visitor| event.record(visitor)));
-
-        let event = PreparedEvent {
-            timestamp: now(),
-            level: *event.metadata().level(),
-            trace_id: tls::try_trace_id(),
-            object: tls::try_meta(),
-            span_id: event.parent().or_else(|| current_span.id()).cloned(),
+        let is_written = self.shared.filter.load().is_written(event.metadata());
+
+        let span_id = event.parent().or_else(|| current_span.id());
+
+        let prepared = |payload_id, span_id: Option<&span::Id>| PreparedEvent {
+            timestamp: self.now(),
+            level,
+            trace_id,
+            object: object.clone(),
+            span_id: span_id.cloned(),
             payload_id,
         };
 
-        // TODO: count missed events.
-        let _ = self.shared.channel.try_send(event);
+        if !is_written {
+            if let Some(payload_id) = self.prepare_detached(event, span_id) {
+                self.capture(prepared(payload_id, None));
+            }
+        }
+
+        if let (Level::ERROR, Some(trace_id)) = (level, trace_id) {
+            for event in self.shared.chains.take(trace_id) {
+                self.send(event);
+            }
+        }
+
+        if !is_written {
+            return;
+        }
+
+        let actor = object
+            .as_ref()
+            .map_or(0, |object| Arc::as_ptr(object) as usize);
+        let key = (event.metadata().callsite(), actor);
+        let (verdict, collapsed) = self.shared.limiter.check(
+            key.clone(),
+            Instant::now(),
+            &self.shared.pool,
+            &self.shared.dropped,
+        );
+
+        if let Some(collapsed) = collapsed {
+            self.send(collapsed);
+        }
+
+        if let Verdict::Skip = verdict {
+            return;
+        }
+
+        let is_kept = matches!(verdict, Verdict::Keep);
+        let payload_id = if is_kept {
+            self.prepare_detached(event, span_id)
+        } else {
+            self.prepare(true, |visitor| event.record(visitor))
+        };
+
+        let payload_id = match payload_id {
+            Some(payload_id) => payload_id,
+            None => {
+                if is_kept {
+                    self.shared.limiter.cancel_keep(&key);
+                }
+                return self.shared.dropped.add(level, object.as_deref());
+            }
+        };
+
+        if is_kept {
+            let event = prepared(payload_id, None);
+            let shared = &self.shared;
+            shared
+                .limiter
+                .keep(key, event, &shared.pool, &shared.dropped);
+        } else {
+            self.send(prepared(payload_id, span_id));
+        }
     }
 
     fn on_close(&self, id: span::Id, _: Context<'_, S>) {
@@ -83,14 +249,3 @@ impl<S: Subscriber> Layer<S> for PrintLayer {
         }
     }
 }
-
-// TODO: use the `quanta` crate.
-#[cfg(not(test))]
-fn now() -> SystemTime {
-    SystemTime::now()
-}
-
-#[cfg(test)]
-fn now() -> SystemTime {
-    humantime::parse_rfc3339("2021-05-17T20:20:20.123456789Z").unwrap()
-}
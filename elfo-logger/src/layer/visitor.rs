@@ -3,44 +3,45 @@ use std::{
     fmt::{self, Write},
 };
 
-use sharded_slab::Pool;
 use tracing::field::{Field, Visit};
 
-use crate::Shared;
+use crate::payload::{FieldValue, Payload};
 
 const MAX_ERROR_SOURCES: u8 = 5;
 
 pub(super) struct Visitor<'a> {
-    pool: &'a Pool<String>,
-    output: &'a mut String,
+    output: &'a mut Payload,
     simplify_message: bool,
 }
 
 impl<'a> Visitor<'a> {
-    pub(super) fn new(shared: &'a Shared, output: &'a mut String, simplify_message: bool) -> Self {
+    pub(super) fn new(output: &'a mut Payload, simplify_message: bool) -> Self {
         Self {
-            pool: &shared.pool,
             output,
             simplify_message,
         }
     }
 
-    pub(super) fn push(&mut self, str: &str) {
-        self.output.push_str(str);
+    pub(super) fn extend_from(&mut self, payload: &Payload) {
+        self.output.extend_from(payload);
     }
 }
 
 impl Visit for Visitor<'_> {
     fn record_i64(&mut self, field: &Field, value: i64) {
-        let _ = write!(self.output, "\t{}={}", field.name(), value);
+        self.output.push(field.name(), FieldValue::I64(value));
     }
 
     fn record_u64(&mut self, field: &Field, value: u64) {
-        let _ = write!(self.output, "\t{}={}", field.name(), value);
+        self.output.push(field.name(), FieldValue::U64(value));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.output.push(field.name(), FieldValue::F64(value));
     }
 
     fn record_bool(&mut self, field: &Field, value: bool) {
-        let _ = write!(self.output, "\t{}={}", field.name(), value);
+        self.output.push(field.name(), FieldValue::Bool(value));
     }
 
     fn record_str(&mut self, field: &Field, value: &str) {
@@ -53,19 +54,19 @@ impl Visit for Visitor<'_> {
 
         if name == "message" && self.simplify_message {
             self.simplify_message = false;
-            self.output.insert_str(0, value);
+            self.output.message.push_str(value);
         } else {
-            let _ = write!(self.output, "\t{}={}", name, value);
+            self.output.push(name, FieldValue::Str(value.into()));
         }
     }
 
     fn record_error(&mut self, field: &Field, mut value: &(dyn Error + 'static)) {
         for i in 0..=MAX_ERROR_SOURCES {
-            let prev_len = self.output.len();
-            let suffix = Repeat(".source", i);
+            let mut formatted = String::new();
 
-            if write!(self.output, "\t{}{}={}", field.name(), suffix, value).is_err() {
-                self.output.truncate(prev_len);
+            if write!(formatted, "{}", value).is_ok() {
+                let name = format!("{}{}", field.name(), Repeat(".source", i));
+                self.output.push(name, FieldValue::Str(formatted));
             }
 
             if let Some(source) = value.source() {
@@ -77,31 +78,19 @@ impl Visit for Visitor<'_> {
     }
 
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
-        let prev_len = self.output.len();
+        if field.name() == "message" && self.simplify_message {
+            self.simplify_message = false;
+            let prev_len = self.output.message.len();
 
-        let result = match field.name() {
-            "message" if self.simplify_message && self.output.is_empty() => {
-                self.simplify_message = false;
-                write!(self.output, "{:?}", value)
+            if write!(self.output.message, "{:?}", value).is_err() {
+                self.output.message.truncate(prev_len);
             }
-            "message" if self.simplify_message => {
-                self.simplify_message = false;
-                let mut result = Ok(());
-
-                if let Some(id) = self.pool.create_with(|tmp| {
-                    result = write!(tmp, "{:?}", value);
-                    self.output.insert_str(0, &tmp);
-                }) {
-                    self.pool.clear(id);
-                }
-
-                result
-            }
-            _ => write!(self.output, "\t{}={:?}", field.name(), value),
-        };
+        } else {
+            let mut formatted = String::new();
 
-        if result.is_err() {
-            self.output.truncate(prev_len);
+            if write!(formatted, "{:?}", value).is_ok() {
+                self.output.push(field.name(), FieldValue::Str(formatted));
+            }
         }
     }
 }
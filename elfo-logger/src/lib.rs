@@ -5,41 +5,105 @@ extern crate elfo_utils;
 
 use std::{env, sync::Arc, time::SystemTime};
 
+use arc_swap::ArcSwap;
 use dashmap::DashMap;
 use derive_more::Constructor;
 use futures_intrusive::{buffer::GrowingHeapBuf, channel::GenericChannel};
 use fxhash::FxBuildHasher;
 use parking_lot::RawMutex;
 use sharded_slab::Pool;
-use tracing::{span::Id as SpanId, Level};
+use tracing::{level_filters::LevelFilter, span::Id as SpanId, Level};
 use tracing_subscriber::{prelude::*, registry::Registry, EnvFilter};
 
 use elfo_core::{trace_id::TraceId, Schema, _priv::ObjectMeta};
 
-use crate::{actor::Logger, layer::PrintLayer};
-
-pub use crate::actor::ReopenLogFile;
+use crate::{
+    actor::Logger,
+    clock::Clock,
+    config::FilterConfig,
+    dropped::DroppedEvents,
+    filter::Filter,
+    limiter::RateLimiter,
+    ring::{RingBuffers, Status},
+    sampling::TraceChains,
+};
+
+pub use crate::{
+    actor::ReopenLogFile,
+    format::{
+        EventFormatter, EventView, JsonFormatter, LogfmtFormatter, SpanView, Spans, TextFormatter,
+    },
+    layer::PrintLayer,
+    payload::{Field, FieldValue, Payload},
+    timestamp::Timestamp,
+};
 
 mod actor;
+mod clock;
 mod config;
+mod dropped;
+mod filter;
+mod format;
 mod formatters;
 mod layer;
-mod theme;
+mod limiter;
+mod payload;
+mod ring;
+mod rotation;
+mod sampling;
+mod sink;
+pub mod theme;
+mod timestamp;
 
 const CHANNEL_CAPACITY: usize = 128 * 1024;
 
-type StringId = usize;
+type PayloadId = usize;
 
 struct Shared {
     channel: GenericChannel<RawMutex, PreparedEvent, GrowingHeapBuf<PreparedEvent>>,
-    pool: Pool<String>,
+    pool: Pool<Payload>,
     spans: DashMap<SpanId, SpanData, FxBuildHasher>,
+    filter: ArcSwap<Filter>,
+    dropped: DroppedEvents,
+    limiter: RateLimiter,
+    rings: RingBuffers,
+    chains: TraceChains,
+    /// Used by sinks with `format = "custom"`.
+    formatter: Option<Arc<dyn EventFormatter>>,
+    clock: Clock,
+    /// Used if the config doesn't define the default level.
+    default_level: LevelFilter,
+}
+
+impl Shared {
+    fn send(&self, event: PreparedEvent) {
+        if let Err(err) = self.channel.try_send(event) {
+            let event = err.into_inner();
+            let object = event.object.as_deref();
+            self.dropped.add(event.level, object);
+            self.pool.clear(event.payload_id);
+        }
+    }
+
+    /// Writes the ring buffer of a failed actor or forgets the ring buffer of
+    /// a terminated one.
+    fn on_status_changed(&self, object: &Arc<ObjectMeta>, status: Status) {
+        match status {
+            Status::Failed => {
+                for event in self.rings.take(object) {
+                    self.send(event);
+                }
+            }
+            Status::Terminated => self.rings.remove(object, &self.pool),
+        }
+    }
 }
 
 #[derive(Constructor)]
 struct SpanData {
+    level: Level,
     parent_id: Option<SpanId>,
-    payload_id: StringId,
+    payload_id: PayloadId,
 }
 
 struct PreparedEvent {
@@ -48,14 +112,39 @@ struct PreparedEvent {
     trace_id: Option<TraceId>,
     object: Option<Arc<ObjectMeta>>,
     span_id: Option<SpanId>,
-    payload_id: StringId,
+    payload_id: PayloadId,
 }
 
+/// Returns the layer and the logger's actor group.
 pub fn new() -> (PrintLayer, Schema) {
+    // Other layers are responsible for filtering by default.
+    new_with(LevelFilter::TRACE, None)
+}
+
+/// Like `new()`, but also registers the formatter used by sinks with
+/// `format = "custom"`.
+pub fn new_with_formatter(formatter: impl EventFormatter) -> (PrintLayer, Schema) {
+    new_with(LevelFilter::TRACE, Some(Arc::new(formatter)))
+}
+
+fn new_with(
+    default_level: LevelFilter,
+    formatter: Option<Arc<dyn EventFormatter>>,
+) -> (PrintLayer, Schema) {
+    let filter = Filter::new(&FilterConfig::default(), default_level, LevelFilter::OFF);
+
     let shared = Shared {
         channel: GenericChannel::with_capacity(CHANNEL_CAPACITY),
         pool: Pool::default(),
         spans: DashMap::default(),
+        filter: ArcSwap::from_pointee(filter),
+        dropped: DroppedEvents::default(),
+        limiter: RateLimiter::default(),
+        rings: RingBuffers::default(),
+        chains: TraceChains::default(),
+        formatter,
+        clock: Clock::default(),
+        default_level,
     };
 
     let shared = Arc::new(shared);
@@ -66,13 +155,37 @@ pub fn new() -> (PrintLayer, Schema) {
 }
 
 pub fn init() -> Schema {
+    init_with(None)
+}
+
+/// Like `init()`, but also registers the formatter used by sinks with
+/// `format = "custom"`.
+pub fn init_with_formatter(formatter: impl EventFormatter) -> Schema {
+    init_with(Some(Arc::new(formatter)))
+}
+
+fn init_with(formatter: Option<Arc<dyn EventFormatter>>) -> Schema {
     // TODO: log instead of panicking.
-    let (print_layer, schema) = new();
 
-    let filter_layer = if env::var(EnvFilter::DEFAULT_ENV).is_ok() {
-        EnvFilter::try_from_default_env().expect("invalid env")
+    // `RUST_LOG` limits levels additionally to the config if it's set.
+    let use_env = env::var(EnvFilter::DEFAULT_ENV).is_ok();
+
+    let default_level = if use_env {
+        LevelFilter::TRACE
+    } else {
+        LevelFilter::INFO
+    };
+
+    let (print_layer, schema) = new_with(default_level, formatter);
+
+    // Without `RUST_LOG`, the max level is provided only by the print layer
+    // (levels from the config and captured levels), so disabled callsites
+    // are skipped by `tracing` macros without calling the subscriber.
+    // It's recalculated on config updates by rebuilding the interest cache.
+    let filter_layer = if use_env {
+        Some(EnvFilter::try_from_default_env().expect("invalid env"))
     } else {
-        EnvFilter::try_new("info").unwrap()
+        None
     };
 
     let subscriber = Registry::default().with(filter_layer).with(print_layer);
@@ -0,0 +1,331 @@
+use std::time::Instant;
+
+use arc_swap::ArcSwapOption;
+use dashmap::DashMap;
+use fxhash::FxBuildHasher;
+use sharded_slab::Pool;
+use tracing::callsite::Identifier;
+
+use crate::{
+    config::RateLimitConfig,
+    dropped::DroppedEvents,
+    payload::{FieldValue, Payload},
+    PreparedEvent,
+};
+
+/// A callsite and an actor, identified by its meta.
+pub(crate) type Key = (Identifier, usize);
+
+/// Limits events per callsite and actor using fixed windows.
+#[derive(Default)]
+pub(crate) struct RateLimiter {
+    config: ArcSwapOption<RateLimitConfig>,
+    windows: DashMap<Key, Window, FxBuildHasher>,
+}
+
+struct Window {
+    started_at: Instant,
+    count: u64,
+    /// The first suppressed event, other ones are only counted.
+    suppressed: Option<PreparedEvent>,
+    repeated: u64,
+}
+
+pub(crate) enum Verdict {
+    Pass,
+    /// The event is suppressed, but should be prepared and kept by `keep()`.
+    Keep,
+    Skip,
+}
+
+impl RateLimiter {
+    pub(crate) fn configure(&self, config: Option<RateLimitConfig>) {
+        self.config.store(config.map(Into::into));
+    }
+
+    /// Also returns an event collapsed in the previous window if it's closed.
+    pub(crate) fn check(
+        &self,
+        key: Key,
+        now: Instant,
+        pool: &Pool<Payload>,
+        dropped: &DroppedEvents,
+    ) -> (Verdict, Option<PreparedEvent>) {
+        let config = self.config.load();
+        let config = ward!(config.as_ref(), return (Verdict::Pass, None));
+
+        let mut window = self.windows.entry(key).or_insert_with(|| Window {
+            started_at: now,
+            count: 0,
+            suppressed: None,
+            repeated: 0,
+        });
+
+        let mut collapsed = None;
+        if now.saturating_duration_since(window.started_at) >= config.window {
+            collapsed = window.collapse(pool, dropped);
+            window.started_at = now;
+            window.count = 0;
+        }
+
+        window.count += 1;
+
+        let verdict = if window.count <= config.max_events {
+            Verdict::Pass
+        } else if window.suppressed.is_none() && window.repeated == 0 {
+            window.repeated += 1;
+            Verdict::Keep
+        } else {
+            window.repeated += 1;
+            Verdict::Skip
+        };
+
+        (verdict, collapsed)
+    }
+
+    pub(crate) fn keep(
+        &self,
+        key: Key,
+        event: PreparedEvent,
+        pool: &Pool<Payload>,
+        dropped: &DroppedEvents,
+    ) {
+        match self.windows.get_mut(&key) {
+            Some(mut window) if window.suppressed.is_none() => window.suppressed = Some(event),
+            // The event is already counted as repeated.
+            Some(_) => {
+                pool.clear(event.payload_id);
+            }
+            // The window has been flushed with the repeat.
+            None => {
+                dropped.add(event.level, event.object.as_deref());
+                pool.clear(event.payload_id);
+            }
+        }
+    }
+
+    /// Called if the event cannot be prepared for `keep()`, the next repeat
+    /// is kept instead.
+    pub(crate) fn cancel_keep(&self, key: &Key) {
+        if let Some(mut window) = self.windows.get_mut(key) {
+            if window.suppressed.is_none() {
+                window.repeated = window.repeated.saturating_sub(1);
+            }
+        }
+    }
+
+    /// Returns events collapsed in closed windows and forgets such windows.
+    pub(crate) fn flush(
+        &self,
+        now: Instant,
+        pool: &Pool<Payload>,
+        dropped: &DroppedEvents,
+    ) -> Vec<PreparedEvent> {
+        let config = self.config.load();
+        let mut collapsed = Vec::new();
+
+        self.windows.retain(|_, window| {
+            let is_open = matches!(
+                config.as_ref(),
+                Some(config) if now.saturating_duration_since(window.started_at) < config.window
+            );
+
+            if !is_open {
+                collapsed.extend(window.collapse(pool, dropped));
+            }
+
+            is_open
+        });
+
+        collapsed
+    }
+}
+
+impl Window {
+    fn collapse(&mut self, pool: &Pool<Payload>, dropped: &DroppedEvents) -> Option<PreparedEvent> {
+        let repeated = std::mem::take(&mut self.repeated);
+        let mut event = self.suppressed.take()?;
+
+        let payload_id = {
+            let old = pool.get(event.payload_id).expect("unknown payload");
+            pool.create_with(|payload| {
+                payload.message.push_str(&old.message);
+                payload.extend_from(&old);
+                payload.push("repeated", FieldValue::U64(repeated));
+            })
+        };
+
+        pool.clear(event.payload_id);
+
+        match payload_id {
+            Some(payload_id) => {
+                event.payload_id = payload_id;
+                Some(event)
+            }
+            None => {
+                dropped.add_many(event.level, event.object.as_deref(), repeated);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::time::{Duration, SystemTime};
+
+    use tracing::{
+        callsite::Callsite, field::FieldSet, metadata::Kind, subscriber::Interest, Level, Metadata,
+    };
+
+    struct TestCallsite;
+
+    impl Callsite for TestCallsite {
+        fn set_interest(&self, _: Interest) {}
+
+        fn metadata(&self) -> &Metadata<'_> {
+            &METADATA
+        }
+    }
+
+    static CALLSITE: TestCallsite = TestCallsite;
+    static METADATA: Metadata<'static> = Metadata::new(
+        "event",
+        "elfo_logger::limiter::tests",
+        Level::ERROR,
+        Some(file!()),
+        Some(line!()),
+        Some(module_path!()),
+        FieldSet::new(&["message"], Identifier(&CALLSITE)),
+        Kind::EVENT,
+    );
+
+    fn event(pool: &Pool<Payload>, message: &str) -> PreparedEvent {
+        let payload_id = pool
+            .create_with(|payload| payload.message.push_str(message))
+            .unwrap();
+
+        PreparedEvent {
+            timestamp: SystemTime::now(),
+            level: Level::ERROR,
+            trace_id: None,
+            object: None,
+            span_id: None,
+            payload_id,
+        }
+    }
+
+    fn check(limiter: &RateLimiter, pool: &Pool<Payload>, now: Instant, message: &str) -> bool {
+        let key = (METADATA.callsite(), 0);
+        let dropped = DroppedEvents::default();
+
+        let (verdict, collapsed) = limiter.check(key.clone(), now, pool, &dropped);
+        assert!(collapsed.is_none());
+
+        match verdict {
+            Verdict::Pass => true,
+            Verdict::Keep => {
+                limiter.keep(key, event(pool, message), pool, &dropped);
+                false
+            }
+            Verdict::Skip => false,
+        }
+    }
+
+    #[test]
+    fn it_collapses_repeats() {
+        let pool = Pool::default();
+        let limiter = RateLimiter::default();
+        let start = Instant::now();
+
+        // Unlimited by default.
+        for _ in 0..5 {
+            assert!(check(&limiter, &pool, start, "a"));
+        }
+
+        limiter.configure(Some(RateLimitConfig {
+            max_events: 2,
+            window: Duration::from_secs(1),
+        }));
+
+        assert!(check(&limiter, &pool, start, "a"));
+        assert!(check(&limiter, &pool, start, "b"));
+        assert!(!check(&limiter, &pool, start, "c"));
+        assert!(!check(&limiter, &pool, start, "d"));
+        assert!(!check(&limiter, &pool, start, "e"));
+
+        // The window isn't closed yet.
+        let dropped = DroppedEvents::default();
+        assert!(limiter.flush(start, &pool, &dropped).is_empty());
+
+        let collapsed = limiter.flush(start + Duration::from_secs(1), &pool, &dropped);
+        assert_eq!(collapsed.len(), 1);
+
+        let payload = pool.get(collapsed[0].payload_id).unwrap();
+        assert_eq!(payload.message, "c");
+        assert_eq!(payload.fields.len(), 1);
+        assert_eq!(payload.fields[0].name, "repeated");
+        assert!(matches!(payload.fields[0].value, FieldValue::U64(3)));
+
+        // A new window.
+        let now = start + Duration::from_secs(2);
+        assert!(check(&limiter, &pool, now, "f"));
+        assert!(check(&limiter, &pool, now, "g"));
+        assert!(!check(&limiter, &pool, now, "h"));
+
+        // The collapsed event is returned by the first event in the next window.
+        let key = (METADATA.callsite(), 0);
+        let now = start + Duration::from_secs(3);
+        let (verdict, collapsed) = limiter.check(key, now, &pool, &dropped);
+        assert!(matches!(verdict, Verdict::Pass));
+        let payload = pool.get(collapsed.unwrap().payload_id).unwrap();
+        assert_eq!(payload.message, "h");
+        assert!(matches!(payload.fields[0].value, FieldValue::U64(1)));
+        assert!(dropped.take().is_empty());
+    }
+
+    #[test]
+    fn it_keeps_next_repeat_if_preparing_fails() {
+        let pool = Pool::default();
+        let limiter = RateLimiter::default();
+        let dropped = DroppedEvents::default();
+        let key = (METADATA.callsite(), 0);
+        let start = Instant::now();
+
+        limiter.configure(Some(RateLimitConfig {
+            max_events: 1,
+            window: Duration::from_secs(1),
+        }));
+
+        assert!(check(&limiter, &pool, start, "a"));
+
+        // The pool is full, so the event isn't prepared.
+        let (verdict, _) = limiter.check(key.clone(), start, &pool, &dropped);
+        assert!(matches!(verdict, Verdict::Keep));
+        limiter.cancel_keep(&key);
+
+        assert!(!check(&limiter, &pool, start, "b"));
+        assert!(!check(&limiter, &pool, start, "c"));
+
+        let collapsed = limiter.flush(start + Duration::from_secs(1), &pool, &dropped);
+        let payload = pool.get(collapsed[0].payload_id).unwrap();
+        assert_eq!(payload.message, "b");
+        assert!(matches!(payload.fields[0].value, FieldValue::U64(2)));
+
+        // The window has been flushed before the repeat is kept.
+        let (verdict, _) = limiter.check(key.clone(), start, &pool, &dropped);
+        assert!(matches!(verdict, Verdict::Pass));
+        let (verdict, _) = limiter.check(key.clone(), start, &pool, &dropped);
+        assert!(matches!(verdict, Verdict::Keep));
+        assert!(limiter
+            .flush(start + Duration::from_secs(1), &pool, &dropped)
+            .is_empty());
+        limiter.keep(key, event(&pool, "d"), &pool, &dropped);
+
+        let dropped = dropped.take();
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].counts, vec![(Level::ERROR, 1)]);
+    }
+}
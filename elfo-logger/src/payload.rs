@@ -0,0 +1,72 @@
+use std::borrow::Cow;
+
+use sharded_slab::Clear;
+
+/// Fields of an event or a span.
+#[derive(Debug, Default)]
+pub struct Payload {
+    /// The message of an event. Spans have no messages, their `message`
+    /// fields are stored as usual fields.
+    pub(crate) message: String,
+    pub(crate) fields: Vec<Field>,
+}
+
+#[derive(Debug)]
+pub struct Field {
+    /// Usually static, but error sources have names like `error.source`.
+    pub(crate) name: Cow<'static, str>,
+    pub(crate) value: FieldValue,
+}
+
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum FieldValue {
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Bool(bool),
+    /// Strings and values formatted by `Display` or `Debug`.
+    Str(String),
+}
+
+impl Payload {
+    /// Empty for spans.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    pub(crate) fn push(&mut self, name: impl Into<Cow<'static, str>>, value: FieldValue) {
+        self.fields.push(Field {
+            name: name.into(),
+            value,
+        });
+    }
+
+    pub(crate) fn extend_from(&mut self, other: &Payload) {
+        self.fields.extend(other.fields.iter().map(|field| Field {
+            name: field.name.clone(),
+            value: field.value.clone(),
+        }));
+    }
+}
+
+impl Field {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &FieldValue {
+        &self.value
+    }
+}
+
+impl Clear for Payload {
+    fn clear(&mut self) {
+        self.message.clear();
+        self.fields.clear();
+    }
+}
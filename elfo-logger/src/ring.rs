@@ -0,0 +1,110 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use arc_swap::ArcSwapOption;
+use dashmap::DashMap;
+use fxhash::FxBuildHasher;
+use sharded_slab::Pool;
+use tracing::{level_filters::LevelFilter, Level};
+
+use elfo_core::{ActorStatus, _priv::ObjectMeta};
+
+use crate::{config::RingBufferConfig, payload::Payload, PreparedEvent};
+
+/// Keeps recent events of actors that aren't written because of levels,
+/// in order to write them if the actor fails.
+#[derive(Default)]
+pub(crate) struct RingBuffers {
+    config: ArcSwapOption<RingBufferConfig>,
+    /// Actors are identified by their meta.
+    rings: DashMap<usize, Ring, FxBuildHasher>,
+}
+
+struct Ring {
+    /// Holds the meta in order to keep the key unique.
+    _object: Arc<ObjectMeta>,
+    events: VecDeque<PreparedEvent>,
+}
+
+pub(crate) enum Status {
+    Failed,
+    Terminated,
+}
+
+impl Status {
+    /// Only statuses affecting ring buffers are returned.
+    pub(crate) fn from_actor_status(status: &ActorStatus) -> Option<Self> {
+        if status.is_failed() {
+            Some(Status::Failed)
+        } else if status.is_terminated() {
+            Some(Status::Terminated)
+        } else {
+            None
+        }
+    }
+}
+
+impl RingBuffers {
+    pub(crate) fn configure(&self, config: Option<RingBufferConfig>, pool: &Pool<Payload>) {
+        if config.is_none() {
+            self.rings.retain(|_, ring| {
+                ring.clear(pool);
+                false
+            });
+        }
+
+        self.config.store(config.map(Into::into));
+    }
+
+    pub(crate) fn accepts(&self, level: Level) -> bool {
+        matches!(&*self.config.load(), Some(config) if level <= LevelFilter::from(config.level))
+    }
+
+    pub(crate) fn push(&self, event: PreparedEvent, pool: &Pool<Payload>) {
+        let config = self.config.load();
+        let (capacity, object) = match (config.as_ref(), &event.object) {
+            (Some(config), Some(object)) if config.capacity > 0 => (config.capacity, object),
+            _ => {
+                pool.clear(event.payload_id);
+                return;
+            }
+        };
+
+        let mut ring = self.rings.entry(key(object)).or_insert_with(|| Ring {
+            _object: object.clone(),
+            events: VecDeque::with_capacity(capacity),
+        });
+
+        // The capacity can be decreased by reconfiguration.
+        while ring.events.len() >= capacity {
+            let evicted = ring.events.pop_front().expect("invalid capacity");
+            pool.clear(evicted.payload_id);
+        }
+
+        ring.events.push_back(event);
+    }
+
+    /// Removes the ring of the actor and returns its events.
+    pub(crate) fn take(&self, object: &Arc<ObjectMeta>) -> Vec<PreparedEvent> {
+        self.rings
+            .remove(&key(object))
+            .map_or_else(Vec::new, |(_, ring)| ring.events.into())
+    }
+
+    pub(crate) fn remove(&self, object: &Arc<ObjectMeta>, pool: &Pool<Payload>) {
+        if let Some((_, mut ring)) = self.rings.remove(&key(object)) {
+            ring.clear(pool);
+        }
+    }
+}
+
+impl Ring {
+    fn clear(&mut self, pool: &Pool<Payload>) {
+        for event in self.events.drain(..) {
+            pool.clear(event.payload_id);
+        }
+    }
+}
+
+fn key(object: &Arc<ObjectMeta>) -> usize {
+    Arc::as_ptr(object) as usize
+}
@@ -0,0 +1,329 @@
+use std::{
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use flate2::{write::GzEncoder, Compression};
+use parking_lot::{const_mutex, Mutex};
+use tokio::{fs::File, io::AsyncWriteExt};
+use tracing::error;
+
+use crate::config::RotationConfig;
+
+/// Serializes compression and removal of rotated files. Otherwise, a file
+/// being compressed after one rotation can be counted twice (with its `.gz`)
+/// or removed while compressing by pruning after the next rotation.
+static MAINTENANCE: Mutex<()> = const_mutex(());
+
+/// The currently written log file.
+pub(crate) struct LogFile {
+    file: File,
+    size: u64,
+    /// The file can be reopened (on restarts, `ReopenLogFile` and config
+    /// updates), so its age is taken from the file system. `None` if the
+    /// creation time isn't supported, then the age isn't limited.
+    created_at: Option<SystemTime>,
+}
+
+impl LogFile {
+    pub(crate) async fn new(file: File) -> Self {
+        let meta = file.metadata().await.ok();
+        let size = meta.as_ref().map_or(0, |meta| meta.len());
+        let created_at = meta.and_then(|meta| meta.created().ok());
+
+        Self {
+            file,
+            size,
+            created_at,
+        }
+    }
+
+    pub(crate) fn created_at(&self) -> Option<SystemTime> {
+        self.created_at
+    }
+
+    pub(crate) async fn write(&mut self, buffer: &str) -> io::Result<()> {
+        self.file.write_all(buffer.as_ref()).await?;
+        self.size += buffer.len() as u64;
+        Ok(())
+    }
+
+    pub(crate) fn need_rotation(&self, config: &RotationConfig) -> bool {
+        let too_big = matches!(config.max_size, Some(max_size) if self.size >= max_size.as_u64());
+
+        let age = self
+            .created_at
+            .and_then(|created_at| created_at.elapsed().ok());
+        let too_old = matches!((config.max_age, age), (Some(max_age), Some(age)) if age >= max_age);
+
+        too_big || too_old
+    }
+
+    /// Renames the file to `<path>.<timestamp>`, then compresses it and
+    /// removes the oldest rotated files in the background, one rotation
+    /// at a time.
+    ///
+    /// The caller is responsible for opening a new file at `path`.
+    pub(crate) async fn rotate(mut self, path: &Path, config: &RotationConfig) {
+        // Wait for all pending writes, otherwise they can be lost.
+        if let Err(err) = self.file.flush().await {
+            error!(error = %err, "cannot flush the log file");
+        }
+        drop(self.file);
+
+        let rotated = vacant_rotated_path(path, SystemTime::now());
+        if let Err(err) = tokio::fs::rename(path, &rotated).await {
+            error!(error = %err, path = %rotated.display(), "cannot rotate the log file");
+            return;
+        }
+
+        let path = path.to_owned();
+        let compress = config.compress;
+        let keep = config.keep;
+
+        tokio::task::spawn_blocking(move || {
+            let _guard = MAINTENANCE.lock();
+
+            if compress {
+                match compress_file(&rotated) {
+                    // Jobs can be run out of order, so the file can be
+                    // already removed by pruning after a later rotation.
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                    Err(err) => {
+                        error!(error = %err, path = %rotated.display(), "cannot compress the log file");
+                    }
+                    Ok(()) => {}
+                }
+            }
+
+            if let Err(err) = remove_old_files(&path, keep) {
+                error!(error = %err, "cannot remove old log files");
+            }
+        });
+    }
+}
+
+fn rotated_path(path: &Path, now: SystemTime, attempt: u32) -> PathBuf {
+    // "2021-05-17T20:20:20.123Z" -> "2021-05-17T202020.123Z", because
+    // colons aren't allowed in file names on some platforms.
+    let timestamp = humantime::format_rfc3339_millis(now)
+        .to_string()
+        .replace(':', "");
+
+    let mut rotated = path.as_os_str().to_owned();
+    rotated.push(".");
+    rotated.push(timestamp);
+
+    // `_` goes after `.`, so such files are sorted after `<timestamp>.gz`.
+    if attempt > 0 {
+        rotated.push(format!("_{}", attempt));
+    }
+
+    rotated.into()
+}
+
+/// Returns the first rotated path that isn't taken by another rotated file
+/// (compressed or not), because several rotations can happen in the same
+/// millisecond.
+fn vacant_rotated_path(path: &Path, now: SystemTime) -> PathBuf {
+    (0..)
+        .map(|attempt| rotated_path(path, now, attempt))
+        .find(|rotated| {
+            let mut compressed = rotated.as_os_str().to_owned();
+            compressed.push(".gz");
+            !rotated.exists() && !Path::new(&compressed).exists()
+        })
+        .expect("infinite iterator")
+}
+
+fn compress_file(path: &Path) -> io::Result<()> {
+    let mut compressed = path.as_os_str().to_owned();
+    compressed.push(".gz");
+
+    let mut input = fs::File::open(path)?;
+    let output = fs::File::create(&compressed)?;
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+
+    fs::remove_file(path)
+}
+
+/// Keeps only `keep` the most recent rotated files.
+fn remove_old_files(path: &Path, keep: usize) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let rotated = rotated_files(path, dir)?;
+    for name in rotated.iter().rev().skip(keep) {
+        fs::remove_file(dir.join(name))?;
+    }
+
+    Ok(())
+}
+
+/// Returns names of rotated files sorted from the oldest to the newest one.
+fn rotated_files(path: &Path, dir: &Path) -> io::Result<Vec<OsString>> {
+    let mut prefix = ward!(path.file_name(), return Ok(Vec::new())).to_owned();
+    prefix.push(".");
+    let prefix = ward!(prefix.to_str(), return Ok(Vec::new()));
+
+    let mut names = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let name = entry?.file_name();
+        // Timestamps start with a year.
+        let is_rotated = matches!(
+            name.to_str(),
+            Some(name) if name.starts_with(prefix)
+                && name[prefix.len()..].starts_with(|c: char| c.is_ascii_digit())
+        );
+
+        if is_rotated {
+            names.push(name);
+        }
+    }
+
+    // Timestamps are sorted lexicographically.
+    names.sort();
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotated_path_should_contain_timestamp() {
+        let now = humantime::parse_rfc3339("2021-05-17T20:20:20.123456789Z").unwrap();
+        assert_eq!(
+            rotated_path(Path::new("logs/app.log"), now, 0),
+            PathBuf::from("logs/app.log.2021-05-17T202020.123Z")
+        );
+        assert_eq!(
+            rotated_path(Path::new("logs/app.log"), now, 2),
+            PathBuf::from("logs/app.log.2021-05-17T202020.123Z_2")
+        );
+    }
+
+    #[test]
+    fn it_never_overwrites_rotated_files() {
+        let dir = std::env::temp_dir().join(format!("elfo-logger-vacant-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        let now = SystemTime::now();
+
+        let first = vacant_rotated_path(&path, now);
+        fs::write(&first, "").unwrap();
+        let second = vacant_rotated_path(&path, now);
+        let mut compressed = second.clone().into_os_string();
+        compressed.push(".gz");
+        fs::write(&compressed, "").unwrap();
+        let third = vacant_rotated_path(&path, now);
+
+        let names = rotated_files(&path, &dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(first, rotated_path(&path, now, 0));
+        assert_eq!(second, rotated_path(&path, now, 1));
+        assert_eq!(third, rotated_path(&path, now, 2));
+        // Sorted from the oldest to the newest one.
+        assert_eq!(names.len(), 2);
+        assert_eq!(dir.join(&names[0]), first);
+        assert_eq!(dir.join(&names[1]), PathBuf::from(compressed));
+    }
+
+    #[tokio::test]
+    async fn it_keeps_age_after_reopening() {
+        let path = std::env::temp_dir().join(format!("elfo-logger-age-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let open = || async {
+            let file = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .await
+                .unwrap();
+            LogFile::new(file).await
+        };
+
+        // The creation time isn't supported by the file system.
+        let created_at = match open().await.created_at {
+            Some(created_at) => created_at,
+            None => return fs::remove_file(&path).unwrap(),
+        };
+
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        let reopened = open().await;
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(reopened.created_at, Some(created_at));
+        assert!(created_at.elapsed().unwrap() >= std::time::Duration::from_millis(10));
+    }
+
+    #[test]
+    fn it_compresses_files() {
+        use std::io::Read;
+
+        let path = std::env::temp_dir().join(format!("elfo-logger-gzip-{}", std::process::id()));
+        fs::write(&path, "some events\n").unwrap();
+
+        compress_file(&path).unwrap();
+        assert!(!path.exists());
+
+        let mut compressed = path.into_os_string();
+        compressed.push(".gz");
+
+        let mut content = String::new();
+        let file = fs::File::open(&compressed).unwrap();
+        flate2::read::GzDecoder::new(file)
+            .read_to_string(&mut content)
+            .unwrap();
+        fs::remove_file(&compressed).unwrap();
+
+        assert_eq!(content, "some events\n");
+    }
+
+    #[test]
+    fn it_removes_old_files() {
+        let dir = std::env::temp_dir().join(format!("elfo-logger-rotation-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let names = [
+            "app.log",
+            "app.log.2021-05-17T202020.000Z.gz",
+            "app.log.2021-05-17T202021.000Z.gz",
+            "app.log.2021-05-17T202022.000Z",
+            "app.log.old",
+            "other.log.2021-05-17T202020.000Z",
+        ];
+
+        for name in &names {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        remove_old_files(&dir.join("app.log"), 2).unwrap();
+
+        let mut rest = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        rest.sort();
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            rest,
+            vec![
+                "app.log",
+                "app.log.2021-05-17T202021.000Z.gz",
+                "app.log.2021-05-17T202022.000Z",
+                "app.log.old",
+                "other.log.2021-05-17T202020.000Z",
+            ]
+        );
+    }
+}
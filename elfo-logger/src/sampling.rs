@@ -0,0 +1,170 @@
+use std::{collections::VecDeque, time::Instant};
+
+use arc_swap::ArcSwapOption;
+use dashmap::DashMap;
+use fxhash::FxBuildHasher;
+use sharded_slab::Pool;
+use tracing::{level_filters::LevelFilter, Level};
+
+use elfo_core::trace_id::TraceId;
+
+use crate::{config::TailSamplingConfig, payload::Payload, PreparedEvent};
+
+/// Keeps events that aren't written because of levels per trace, in order to
+/// write the whole chain if an error happens in the trace. Otherwise, events
+/// are discarded after the window.
+#[derive(Default)]
+pub(crate) struct TraceChains {
+    config: ArcSwapOption<TailSamplingConfig>,
+    chains: DashMap<TraceId, Chain, FxBuildHasher>,
+}
+
+struct Chain {
+    started_at: Instant,
+    events: VecDeque<PreparedEvent>,
+}
+
+impl TraceChains {
+    pub(crate) fn configure(&self, config: Option<TailSamplingConfig>, pool: &Pool<Payload>) {
+        if config.is_none() {
+            self.chains.retain(|_, chain| {
+                chain.clear(pool);
+                false
+            });
+        }
+
+        self.config.store(config.map(Into::into));
+    }
+
+    pub(crate) fn accepts(&self, level: Level) -> bool {
+        matches!(&*self.config.load(), Some(config) if level <= LevelFilter::from(config.level))
+    }
+
+    pub(crate) fn push(&self, event: PreparedEvent, now: Instant, pool: &Pool<Payload>) {
+        let config = self.config.load();
+        let (config, trace_id) = match (config.as_ref(), event.trace_id) {
+            (Some(config), Some(trace_id)) if config.capacity > 0 => (config, trace_id),
+            _ => {
+                pool.clear(event.payload_id);
+                return;
+            }
+        };
+
+        let mut chain = self.chains.entry(trace_id).or_insert_with(|| Chain {
+            started_at: now,
+            events: VecDeque::new(),
+        });
+
+        // The capacity can be decreased by reconfiguration.
+        while chain.events.len() >= config.capacity {
+            let evicted = chain.events.pop_front().expect("invalid capacity");
+            pool.clear(evicted.payload_id);
+        }
+
+        chain.events.push_back(event);
+    }
+
+    /// Removes the chain and returns its events.
+    pub(crate) fn take(&self, trace_id: TraceId) -> Vec<PreparedEvent> {
+        self.chains
+            .remove(&trace_id)
+            .map_or_else(Vec::new, |(_, chain)| chain.events.into())
+    }
+
+    /// Discards chains started before the window.
+    pub(crate) fn expire(&self, now: Instant, pool: &Pool<Payload>) {
+        let config = self.config.load();
+
+        self.chains.retain(|_, chain| {
+            let is_alive = matches!(
+                config.as_ref(),
+                Some(config) if now.saturating_duration_since(chain.started_at) < config.window
+            );
+
+            if !is_alive {
+                chain.clear(pool);
+            }
+
+            is_alive
+        });
+    }
+}
+
+impl Chain {
+    fn clear(&mut self, pool: &Pool<Payload>) {
+        for event in self.events.drain(..) {
+            pool.clear(event.payload_id);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{
+        convert::TryFrom,
+        time::{Duration, SystemTime},
+    };
+
+    use crate::config::LogLevel;
+
+    fn event(pool: &Pool<Payload>, trace_id: u64, message: &str) -> PreparedEvent {
+        let payload_id = pool
+            .create_with(|payload| payload.message.push_str(message))
+            .unwrap();
+
+        PreparedEvent {
+            timestamp: SystemTime::now(),
+            level: Level::DEBUG,
+            trace_id: Some(TraceId::try_from(trace_id).unwrap()),
+            object: None,
+            span_id: None,
+            payload_id,
+        }
+    }
+
+    fn messages(pool: &Pool<Payload>, events: Vec<PreparedEvent>) -> Vec<String> {
+        events
+            .into_iter()
+            .map(|event| pool.get(event.payload_id).unwrap().message.clone())
+            .collect()
+    }
+
+    #[test]
+    fn it_keeps_chains_during_window() {
+        let pool = Pool::default();
+        let chains = TraceChains::default();
+        let start = Instant::now();
+        let trace_id = |raw| TraceId::try_from(raw).unwrap();
+
+        chains.configure(
+            Some(TailSamplingConfig {
+                level: LogLevel::Debug,
+                window: Duration::from_secs(5),
+                capacity: 2,
+            }),
+            &pool,
+        );
+
+        assert!(chains.accepts(Level::DEBUG));
+        assert!(!chains.accepts(Level::TRACE));
+
+        chains.push(event(&pool, 1, "a"), start, &pool);
+        chains.push(event(&pool, 2, "b"), start, &pool);
+        chains.push(event(&pool, 1, "c"), start, &pool);
+        chains.push(event(&pool, 1, "d"), start, &pool);
+
+        // The oldest events are evicted.
+        assert_eq!(messages(&pool, chains.take(trace_id(1))), vec!["c", "d"]);
+        assert!(chains.take(trace_id(1)).is_empty());
+
+        chains.expire(start + Duration::from_secs(4), &pool);
+        chains.push(event(&pool, 3, "e"), start + Duration::from_secs(4), &pool);
+        chains.expire(start + Duration::from_secs(5), &pool);
+
+        // Only the chain started in the window is alive.
+        assert!(chains.take(trace_id(2)).is_empty());
+        assert_eq!(messages(&pool, chains.take(trace_id(3))), vec!["e"]);
+    }
+}
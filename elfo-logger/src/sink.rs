@@ -0,0 +1,319 @@
+#[cfg(unix)]
+use std::os::unix::net::UnixDatagram;
+use std::{io, path::Path};
+
+use tokio::fs::OpenOptions;
+use tracing::{error, level_filters::LevelFilter, warn, Level};
+
+use crate::{
+    config::{Format, Sink, SinkConfig},
+    rotation::LogFile,
+};
+
+/// How events are formatted, it's the same for sinks with the same format
+/// and coloring, so events are formatted once for such sinks.
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum Style {
+    Plain,
+    Colored,
+    Json,
+    Logfmt,
+    Custom,
+}
+
+impl Style {
+    pub(crate) const COUNT: usize = 5;
+}
+
+pub(crate) struct SinkWriter {
+    config: SinkConfig,
+    style: Style,
+    output: Output,
+}
+
+enum Output {
+    Stdout,
+    Stderr,
+    /// `None` if the file cannot be opened or written, then it's reopened
+    /// on ticks and events are dropped until that.
+    File(Option<LogFile>),
+    /// `None` if the socket is unreachable, then it's reconnected on ticks.
+    #[cfg(unix)]
+    Unix(Option<UnixDatagram>),
+    /// Sinks that cannot be used on this platform.
+    #[cfg(not(unix))]
+    Nothing,
+}
+
+impl SinkWriter {
+    pub(crate) async fn open(config: SinkConfig) -> Self {
+        let output = match config.sink {
+            Sink::Stdout => Output::Stdout,
+            Sink::Stderr => Output::Stderr,
+            Sink::File => {
+                let result = open_file(&config).await;
+                Output::File(report(result, &config, "cannot open the log file"))
+            }
+            #[cfg(unix)]
+            Sink::Unix => {
+                let result = connect(&config);
+                Output::Unix(report(result, &config, "cannot connect to the log socket"))
+            }
+            #[cfg(not(unix))]
+            Sink::Unix => {
+                error!("unix sockets are unsupported on this platform");
+                Output::Nothing
+            }
+        };
+
+        let use_colors = match config.sink {
+            Sink::Stdout => atty::is(atty::Stream::Stdout),
+            Sink::Stderr => atty::is(atty::Stream::Stderr),
+            Sink::File | Sink::Unix => false,
+        };
+
+        let style = match config.format {
+            Format::Text if use_colors => Style::Colored,
+            Format::Text => Style::Plain,
+            Format::Json => Style::Json,
+            Format::Logfmt => Style::Logfmt,
+            Format::Custom => Style::Custom,
+        };
+
+        Self {
+            config,
+            style,
+            output,
+        }
+    }
+
+    pub(crate) fn style(&self) -> Style {
+        self.style
+    }
+
+    pub(crate) fn accepts(&self, level: Level) -> bool {
+        // More verbose levels are greater.
+        !matches!(self.config.level, Some(threshold) if level > LevelFilter::from(threshold))
+    }
+
+    /// Returns `false` if the event is dropped by the sink.
+    pub(crate) async fn write(&mut self, buffer: &str) -> bool {
+        match &mut self.output {
+            Output::Stdout => print!("{}", buffer),
+            Output::Stderr => eprint!("{}", buffer),
+            Output::File(file) => {
+                let log_file = ward!(file.as_mut(), return false);
+
+                // TODO: what about performance here?
+                if let Err(err) = log_file.write(buffer).await {
+                    let path = self.config.path.as_deref().map(Path::display);
+                    error!(error = %err, path = ?path, "cannot write to the log file");
+                    *file = None;
+                    return false;
+                }
+
+                // Events aren't lost, they are waiting in the channel.
+                self.rotate_if_needed().await;
+            }
+            #[cfg(unix)]
+            Output::Unix(socket) => {
+                let connected = ward!(socket.as_ref(), return false);
+
+                match connected.send(buffer.as_bytes()) {
+                    Ok(_) => {}
+                    // The socket is full, the event is dropped.
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => return false,
+                    // Nobody listens to the socket, it's reconnected on ticks.
+                    Err(_) => {
+                        *socket = None;
+                        return false;
+                    }
+                }
+            }
+            #[cfg(not(unix))]
+            Output::Nothing => {}
+        }
+
+        true
+    }
+
+    async fn rotate_if_needed(&mut self) {
+        let rotation = ward!(&self.config.rotation);
+        let path = ward!(&self.config.path);
+
+        if let Output::File(file) = &mut self.output {
+            if matches!(file, Some(file) if file.need_rotation(rotation)) {
+                file.take().expect("checked").rotate(path, rotation).await;
+                let result = open_file(&self.config).await;
+                *file = report(result, &self.config, "cannot reopen the log file");
+            }
+        }
+    }
+
+    /// Reopens the file or reconnects the socket if it has been closed
+    /// because of errors. Errors aren't logged here to avoid flooding logs.
+    pub(crate) async fn reopen_if_closed(&mut self) {
+        match &mut self.output {
+            Output::File(file @ None) => *file = open_file(&self.config).await.ok(),
+            #[cfg(unix)]
+            Output::Unix(socket @ None) => *socket = connect(&self.config).ok(),
+            _ => {}
+        }
+    }
+}
+
+fn report<T>(result: io::Result<T>, config: &SinkConfig, message: &str) -> Option<T> {
+    result
+        .map_err(|err| {
+            let path = config.path.as_deref().map(Path::display);
+            error!(error = %err, path = ?path, "{}", message);
+        })
+        .ok()
+}
+
+async fn open_file(config: &SinkConfig) -> io::Result<LogFile> {
+    let path = config.path.as_ref().expect("validated by the config");
+
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+
+    let file = LogFile::new(file).await;
+
+    let max_age = config
+        .rotation
+        .as_ref()
+        .and_then(|rotation| rotation.max_age);
+    if max_age.is_some() && file.created_at().is_none() {
+        warn!(
+            path = %path.display(),
+            "the creation time of files is unsupported by the file system, `max_age` is ignored"
+        );
+    }
+
+    Ok(file)
+}
+
+#[cfg(unix)]
+fn connect(config: &SinkConfig) -> io::Result<UnixDatagram> {
+    let path = config.path.as_ref().expect("validated by the config");
+
+    // Nonblocking, because the logger mustn't wait for a slow reader.
+    let socket = UnixDatagram::unbound()?;
+    socket.connect(path)?;
+    socket.set_nonblocking(true)?;
+    Ok(socket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::config::LogLevel;
+
+    fn config(sink: Sink) -> SinkConfig {
+        SinkConfig {
+            sink,
+            path: None,
+            format: Format::Json,
+            level: None,
+            rotation: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn it_filters_by_level() {
+        let sink = SinkWriter::open(config(Sink::Stderr)).await;
+        assert!(sink.accepts(Level::TRACE));
+
+        let mut config = config(Sink::Stderr);
+        config.level = Some(LogLevel::Warn);
+        let sink = SinkWriter::open(config).await;
+        assert!(sink.accepts(Level::ERROR));
+        assert!(sink.accepts(Level::WARN));
+        assert!(!sink.accepts(Level::INFO));
+        assert!(!sink.accepts(Level::TRACE));
+
+        let mut off = self::config(Sink::Stderr);
+        off.level = Some(LogLevel::Off);
+        let sink = SinkWriter::open(off).await;
+        assert!(!sink.accepts(Level::ERROR));
+    }
+
+    #[tokio::test]
+    async fn it_reopens_unavailable_files() {
+        let dir = std::env::temp_dir().join(format!("elfo-logger-dir-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("app.log");
+
+        let mut config = config(Sink::File);
+        config.path = Some(path.clone());
+        let mut sink = SinkWriter::open(config).await;
+        assert!(!sink.write("lost\n").await);
+
+        std::fs::create_dir(&dir).unwrap();
+        sink.reopen_if_closed().await;
+        assert!(sink.write("written\n").await);
+
+        // `tokio::fs::File` writes in the background.
+        let mut content = String::new();
+        for _ in 0..100 {
+            content = std::fs::read_to_string(&path).unwrap();
+            if !content.is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(content, "written\n");
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn it_writes_to_unix_socket() {
+        let path = std::env::temp_dir().join(format!("elfo-logger-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let receiver = tokio::net::UnixDatagram::bind(&path).unwrap();
+
+        let mut config = config(Sink::Unix);
+        config.path = Some(path.clone());
+        let mut sink = SinkWriter::open(config).await;
+        assert!(sink.write("{\"message\":\"hello\"}\n").await);
+
+        let mut buffer = [0; 64];
+        let len = receiver.recv(&mut buffer).await.unwrap();
+        assert_eq!(&buffer[..len], b"{\"message\":\"hello\"}\n");
+
+        // The logger doesn't wait for a slow reader, events are dropped.
+        let mut written = 0;
+        while sink.write("{\"message\":\"spam\"}\n").await {
+            written += 1;
+            assert!(written < 100_000, "the socket must become full");
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn it_reconnects_to_unix_socket() {
+        let path =
+            std::env::temp_dir().join(format!("elfo-logger-late-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let mut config = config(Sink::Unix);
+        config.path = Some(path.clone());
+        let mut sink = SinkWriter::open(config).await;
+        assert!(!sink.write("lost\n").await);
+
+        let receiver = tokio::net::UnixDatagram::bind(&path).unwrap();
+        sink.reopen_if_closed().await;
+        assert!(sink.write("written\n").await);
+
+        let mut buffer = [0; 64];
+        let len = receiver.recv(&mut buffer).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(&buffer[..len], b"written\n");
+    }
+}
@@ -1,30 +1,35 @@
-use std::{sync::Arc, time::SystemTime};
+use std::sync::Arc;
 
 use tracing::Level;
 
 use elfo_core::{_priv::ObjectMeta, trace_id::TraceId};
 
-use crate::formatters::*;
+pub use crate::formatters::{
+    ColoredByHash, ColoredLevel, ColoredPayload, EmptyIfNone, Formatter, PlainPayload, Rfc3339Weak,
+};
 
-pub(crate) trait Theme {
-    type Timestamp: Formatter<SystemTime>;
+use crate::{payload, timestamp::Timestamp};
+
+/// Parts of events formatted by `TextFormatter`.
+pub trait Theme {
+    type Timestamp: Formatter<Timestamp>;
     type Level: Formatter<Level>;
     type TraceId: Formatter<Option<TraceId>>;
     type ObjectMeta: Formatter<Option<Arc<ObjectMeta>>>;
-    type Payload: Formatter<str>;
+    type Payload: Formatter<payload::Payload>;
 }
 
-pub(crate) struct PlainTheme;
+pub struct PlainTheme;
 
 impl Theme for PlainTheme {
     type Level = Level;
     type ObjectMeta = EmptyIfNone<Arc<ObjectMeta>>;
-    type Payload = Payload;
+    type Payload = PlainPayload;
     type Timestamp = Rfc3339Weak;
     type TraceId = EmptyIfNone<TraceId>;
 }
 
-pub(crate) struct ColoredTheme;
+pub struct ColoredTheme;
 
 impl Theme for ColoredTheme {
     type Level = ColoredLevel;
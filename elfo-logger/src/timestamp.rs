@@ -0,0 +1,156 @@
+use std::{
+    fmt::{self, Write},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, Local};
+
+use crate::config::{Precision, TimeZone, TimestampConfig};
+
+/// A timestamp of an event with formatting options from the config.
+///
+/// `Display` writes RFC 3339 (e.g. "2021-05-17T20:20:20.123456789Z") or
+/// seconds since the unix epoch (e.g. "1621282820.123456789").
+#[derive(Clone, Copy)]
+pub struct Timestamp {
+    time: SystemTime,
+    config: TimestampConfig,
+}
+
+impl Timestamp {
+    pub(crate) fn new(time: SystemTime, config: TimestampConfig) -> Self {
+        Self { time, config }
+    }
+
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+
+    /// Whether it's written as a number.
+    pub fn is_unix(&self) -> bool {
+        self.config.unix
+    }
+
+    /// Writes "2021-05-17 20:20:20.123456789" without the time zone.
+    /// Unix timestamps are written as usual.
+    pub fn write_weak(&self, out: &mut String) {
+        self.write(out, true);
+    }
+
+    fn write(&self, out: &mut String, weak: bool) {
+        if self.config.unix {
+            return self.write_unix(out);
+        }
+
+        match self.config.timezone {
+            TimeZone::Utc => {
+                let t_idx = out.len() + 10;
+                let _ = match self.config.precision {
+                    Precision::S => write!(out, "{}", humantime::format_rfc3339_seconds(self.time)),
+                    Precision::Ms => write!(out, "{}", humantime::format_rfc3339_millis(self.time)),
+                    Precision::Us => write!(out, "{}", humantime::format_rfc3339_micros(self.time)),
+                    Precision::Ns => write!(out, "{}", humantime::format_rfc3339_nanos(self.time)),
+                };
+
+                if weak {
+                    // Replace "T" with " ".
+                    out.replace_range(t_idx..t_idx + 1, " ");
+                    // Remove trailing "Z".
+                    out.pop();
+                }
+            }
+            TimeZone::Local => {
+                let fmt = match (weak, self.config.precision) {
+                    (false, Precision::S) => "%Y-%m-%dT%H:%M:%S%:z",
+                    (false, Precision::Ms) => "%Y-%m-%dT%H:%M:%S%.3f%:z",
+                    (false, Precision::Us) => "%Y-%m-%dT%H:%M:%S%.6f%:z",
+                    (false, Precision::Ns) => "%Y-%m-%dT%H:%M:%S%.9f%:z",
+                    (true, Precision::S) => "%Y-%m-%d %H:%M:%S",
+                    (true, Precision::Ms) => "%Y-%m-%d %H:%M:%S%.3f",
+                    (true, Precision::Us) => "%Y-%m-%d %H:%M:%S%.6f",
+                    (true, Precision::Ns) => "%Y-%m-%d %H:%M:%S%.9f",
+                };
+
+                let time: DateTime<Local> = self.time.into();
+                let _ = write!(out, "{}", time.format(fmt));
+            }
+        }
+    }
+
+    fn write_unix(&self, out: &mut String) {
+        let elapsed = self.time.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let secs = elapsed.as_secs();
+        let nanos = elapsed.subsec_nanos();
+
+        let _ = match self.config.precision {
+            Precision::S => write!(out, "{}", secs),
+            Precision::Ms => write!(out, "{}.{:03}", secs, nanos / 1_000_000),
+            Precision::Us => write!(out, "{}.{:06}", secs, nanos / 1_000),
+            Precision::Ns => write!(out, "{}.{:09}", secs, nanos),
+        };
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        self.write(&mut out, false);
+        f.write_str(&out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(config: &str, weak: bool) -> String {
+        let config = toml::from_str(config).unwrap();
+        let time = humantime::parse_rfc3339("2021-05-17T20:20:20.123456789Z").unwrap();
+        let mut out = String::new();
+        Timestamp::new(time, config).write(&mut out, weak);
+        out
+    }
+
+    #[test]
+    fn it_writes_utc_by_default() {
+        assert_eq!(format("", false), "2021-05-17T20:20:20.123456789Z");
+        assert_eq!(format("", true), "2021-05-17 20:20:20.123456789");
+        assert_eq!(
+            format(r#"precision = "ms""#, false),
+            "2021-05-17T20:20:20.123Z"
+        );
+        assert_eq!(format(r#"precision = "s""#, true), "2021-05-17 20:20:20");
+    }
+
+    #[test]
+    fn it_writes_unix_timestamps() {
+        assert_eq!(format("unix = true", false), "1621282820.123456789");
+        assert_eq!(format("unix = true", true), "1621282820.123456789");
+        let config = r#"
+            unix = true
+            precision = "us"
+        "#;
+        assert_eq!(format(config, false), "1621282820.123456");
+        let config = r#"
+            unix = true
+            precision = "s"
+        "#;
+        assert_eq!(format(config, false), "1621282820");
+    }
+
+    #[test]
+    fn it_writes_local_time_with_offset() {
+        let config = r#"
+            timezone = "local"
+            precision = "ms"
+        "#;
+        let out = format(config, false);
+        let time: DateTime<Local> = humantime::parse_rfc3339("2021-05-17T20:20:20.123Z")
+            .unwrap()
+            .into();
+        assert_eq!(
+            out,
+            time.to_rfc3339_opts(chrono::SecondsFormat::Millis, false)
+        );
+    }
+}
@@ -1,5 +1,14 @@
 #![warn(rust_2018_idioms, unreachable_pub)]
 
+pub use logs::LogEvent;
 pub use proxy::{proxy, Proxy};
+pub use recorder::{MetricValue, MetricsSnapshot};
 
+mod logs;
 mod proxy;
+mod recorder;
+
+#[doc(hidden)]
+pub mod _priv {
+    pub use tracing::Level;
+}
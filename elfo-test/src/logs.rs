@@ -0,0 +1,183 @@
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::{self, Display, Write},
+    sync::{Arc, Mutex, Once, Weak},
+};
+
+use tracing::{
+    field::{Field, Visit},
+    Event, Level, Subscriber,
+};
+use tracing_subscriber::{
+    filter::Targets,
+    fmt as print,
+    layer::{Context, Layer},
+    prelude::*,
+    registry::Registry,
+};
+
+use elfo_core::{_priv::current_book_id, tls};
+
+use crate::proxy::SUBJECT;
+
+/// An event emitted by the actor under test.
+#[derive(Debug, Clone)]
+pub struct LogEvent {
+    pub level: Level,
+    pub target: String,
+    /// The key of the actor that has emitted the event.
+    pub key: Option<String>,
+    pub message: String,
+    /// Other fields formatted by `Debug` (strings are written as is).
+    pub fields: Vec<(String, String)>,
+}
+
+impl LogEvent {
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| &value[..])
+    }
+
+    #[doc(hidden)]
+    pub fn matches(&self, name: &str, value: &dyn Display) -> bool {
+        let value = value.to_string();
+
+        match name {
+            "contains" => self.message.contains(&value),
+            "key" => self.key.as_ref() == Some(&value),
+            "target" => self.target == value,
+            _ => self.field(name) == Some(&value[..]),
+        }
+    }
+}
+
+pub(crate) type Logs = Arc<Mutex<Vec<LogEvent>>>;
+
+/// Events of proxies by ids of their topologies, forgotten with proxies.
+static LOGS: Mutex<BTreeMap<usize, Weak<Mutex<Vec<LogEvent>>>>> = Mutex::new(BTreeMap::new());
+
+/// Installs the global subscriber printing events and capturing ones of
+/// actors under test. It's done once, so if another global subscriber has
+/// been set, events aren't captured and it's warned about.
+pub(crate) fn install() {
+    static INSTALL: Once = Once::new();
+
+    INSTALL.call_once(|| {
+        // Events are captured regardless of `RUST_LOG`, which limits only printing.
+        let print_filter = env::var("RUST_LOG")
+            .ok()
+            .and_then(|directives| directives.parse().ok())
+            .unwrap_or_else(|| Targets::new().with_default(Level::ERROR));
+
+        let print_layer = print::layer()
+            .with_target(false)
+            .with_test_writer()
+            .with_filter(print_filter);
+
+        let subscriber = Registry::default().with(print_layer).with(CaptureLayer);
+        if tracing::subscriber::set_global_default(subscriber).is_err() {
+            eprintln!("WARNING: a global subscriber is already set, `Proxy::logs()` are empty");
+        }
+    });
+}
+
+/// Starts capturing events of the actor under test in the topology.
+pub(crate) fn capture(book_id: usize) -> Logs {
+    let logs = Logs::default();
+    let mut stored = LOGS.lock().unwrap();
+    stored.retain(|_, logs| logs.strong_count() > 0);
+    stored.insert(book_id, Arc::downgrade(&logs));
+    logs
+}
+
+/// Records events of the actor under test.
+///
+/// Events are routed to the proxy by the topology of the emitting actor, so
+/// it works with any runtime. Events of tasks spawned by actors are skipped.
+struct CaptureLayer;
+
+impl<S: Subscriber> Layer<S> for CaptureLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let meta = match tls::try_meta() {
+            Some(meta) if meta.group == SUBJECT => meta,
+            _ => return,
+        };
+
+        let logs = current_book_id().and_then(|id| LOGS.lock().unwrap().get(&id)?.upgrade());
+        let logs = match logs {
+            Some(logs) => logs,
+            None => return,
+        };
+
+        let metadata = event.metadata();
+
+        let mut visitor = Visitor::default();
+        event.record(&mut visitor);
+
+        logs.lock().unwrap().push(LogEvent {
+            level: *metadata.level(),
+            target: metadata.target().into(),
+            key: meta.key.clone(),
+            message: visitor.message,
+            fields: visitor.fields,
+        });
+    }
+}
+
+#[derive(Default)]
+struct Visitor {
+    message: String,
+    fields: Vec<(String, String)>,
+}
+
+impl Visit for Visitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            self.fields.push((field.name().into(), value.into()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            self.fields
+                .push((field.name().into(), format!("{:?}", value)));
+        }
+    }
+}
+
+/// Checks that the actor under test has emitted an event with the provided
+/// level (`TRACE`, `DEBUG`, `INFO`, `WARN` or `ERROR`). Other conditions:
+/// * `contains = ".."` — the message contains the substring,
+/// * `key = ".."` — the event is emitted by the actor with the key,
+/// * `target = ".."` — the event has the target,
+/// * `<field> = <value>` — the field is formatted as the value.
+///
+/// ```ignore
+/// assert_logged!(proxy, WARN, contains = "overloaded", key = "shard-1", count = 5);
+/// ```
+#[macro_export]
+macro_rules! assert_logged {
+    ($proxy:expr, $level:ident $(, $name:ident = $value:expr)* $(,)?) => {{
+        let logs = $proxy.logs();
+        let found = logs.iter().any(|event| {
+            event.level == $crate::_priv::Level::$level
+                $(&& event.matches(stringify!($name), &$value))*
+        });
+
+        if !found {
+            panic!(
+                "\nno event matches conditions\nconditions: {}{}\nevents: {:#?}\n",
+                stringify!($level),
+                concat!($(", ", stringify!($name), " = ", stringify!($value)),*),
+                logs,
+            );
+        }
+    }};
+}
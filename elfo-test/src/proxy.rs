@@ -1,5 +1,5 @@
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, VecDeque},
     future,
     sync::{
         atomic::{AtomicUsize, Ordering},
@@ -12,25 +12,37 @@ use std::{
 use futures_intrusive::channel::shared;
 use serde::{de::Deserializer, Deserialize};
 use serde_value::Value;
-use tokio::task;
+use tokio::{task, time};
 
 use elfo_core::{
-    self as elfo, ActorGroup, Addr, Context, Envelope, Local, Message, Request, ResponseToken,
-    Schema,
-    _priv::{do_start, ObjectMeta},
+    self as elfo,
+    _priv::{book_id, do_start, is_group_idle, ObjectMeta},
     routers::{MapRouter, Outcome},
     tls,
-    topology::Topology,
-    trace_id,
+    topology::{GetAddrs, Topology},
+    trace_id, ActorGroup, Addr, Context, Envelope, Local, Message, Request, ResponseToken, Schema,
 };
 use elfo_macros::{message, msg_raw as msg};
 
+use crate::{
+    logs::{self, LogEvent, Logs},
+    recorder::{self, MetricValue, MetricsSnapshot, Storage},
+};
+
+/// The name of the group under test.
+pub(crate) const SUBJECT: &str = "subject";
+
 const MAX_WAIT_TIME: Duration = Duration::from_millis(150);
-const SYNC_YIELD_COUNT: usize = 32;
+const MAX_SYNC_TIME: Duration = Duration::from_secs(5);
 
 pub struct Proxy {
     context: Context,
+    subject: Addr,
     meta: Arc<ObjectMeta>,
+    logs: Logs,
+    metrics: Arc<Storage>,
+    /// Messages skipped by `recv_matching()`.
+    skipped: VecDeque<Envelope>,
     non_exhaustive: bool,
 }
 
@@ -77,14 +89,24 @@ impl Proxy {
             // We are forced to use `std::time::Instant` instead of `tokio::time::Instant`
             // because we don't want to use mocked time by tokio here.
             let start = StdInstant::now();
+            let mut idle_since = start;
 
             while {
                 if let Some(envelope) = self.try_recv() {
                     return envelope;
                 }
 
-                task::yield_now().await;
-                start.elapsed() < MAX_WAIT_TIME
+                self.let_subject_run().await;
+
+                // Only the time when the subject waits for something is counted,
+                // so a busy subject on a loaded machine doesn't lead to a panic.
+                // However, the subject can be busy forever, so the total time is limited too.
+                let now = StdInstant::now();
+                if !self.is_subject_idle() {
+                    idle_since = now;
+                }
+
+                now - idle_since < MAX_WAIT_TIME && now - start < MAX_SYNC_TIME
             } {}
 
             panic!("too long");
@@ -93,19 +115,111 @@ impl Proxy {
     }
 
     pub fn try_recv(&mut self) -> Option<Envelope> {
+        if let Some(envelope) = self.skipped.pop_front() {
+            return Some(envelope);
+        }
+
         // This trace id will be replaced anyway.
         tls::sync_scope(self.meta.clone(), trace_id::generate(), || {
             self.context.try_recv().ok()
         })
     }
 
+    /// Waits for a message at most `timeout` according to `tokio::time`,
+    /// so it's immediate with paused time if the runtime has nothing to do.
+    pub async fn recv_timeout(&mut self, timeout: Duration) -> Option<Envelope> {
+        if let Some(envelope) = self.skipped.pop_front() {
+            return Some(envelope);
+        }
+
+        // This trace id will be replaced anyway.
+        tls::scope(self.meta.clone(), trace_id::generate(), async {
+            time::timeout(timeout, self.context.recv())
+                .await
+                .ok()
+                .flatten()
+        })
+        .await
+    }
+
+    /// Checks that nothing arrives during `duration` according to
+    /// `tokio::time`.
+    pub async fn assert_no_messages_for(&mut self, duration: Duration) {
+        if let Some(envelope) = self.recv_timeout(duration).await {
+            panic!("unexpected message: {:?}", envelope);
+        }
+    }
+
+    /// Like `recv()`, but waits for a message matching the predicate.
+    /// Other messages are kept and returned by next calls in the same order.
+    pub async fn recv_matching(&mut self, mut f: impl FnMut(&Envelope) -> bool) -> Envelope {
+        let mut skipped = Vec::new();
+
+        let envelope = loop {
+            let envelope = self.recv().await;
+
+            if f(&envelope) {
+                break envelope;
+            }
+
+            skipped.push(envelope);
+        };
+
+        for envelope in skipped.into_iter().rev() {
+            self.skipped.push_front(envelope);
+        }
+
+        envelope
+    }
+
     /// Waits until the testable actor handles all previously sent messages.
     ///
-    /// Now it's implemented as multiple calls `yield_now()`,
-    /// but the implementation can be changed in the future.
+    /// It returns once all actors of the subject's group wait for new messages
+    /// in `Context::recv()` and have empty mailboxes. Failed actors are idle
+    /// until the time to restart them. Tasks spawned by the subject aren't
+    /// tracked.
+    ///
+    /// # Panics
+    /// If the subject is still busy after 5 seconds, e.g. it waits for
+    /// a response or sleeps in a handler under real time.
     pub async fn sync(&mut self) {
-        for _ in 0..SYNC_YIELD_COUNT {
+        let start = StdInstant::now();
+
+        while {
+            self.take_incoming();
+            self.let_subject_run().await;
+            !self.is_subject_idle()
+        } {
+            if start.elapsed() > MAX_SYNC_TIME {
+                panic!("the subject is still busy after {:?}", MAX_SYNC_TIME);
+            }
+        }
+    }
+
+    /// Moves received messages to `skipped`, so the subject isn't blocked
+    /// on sending to the full mailbox of the proxy.
+    fn take_incoming(&mut self) {
+        // This trace id will be replaced anyway.
+        tls::sync_scope(self.meta.clone(), trace_id::generate(), || {
+            while let Ok(envelope) = self.context.try_recv() {
+                self.skipped.push_back(envelope);
+            }
+        })
+    }
+
+    fn is_subject_idle(&self) -> bool {
+        is_group_idle(&self.context, self.subject)
+    }
+
+    /// Yields if the subject is idle. Otherwise, sleeps a bit in order to let
+    /// paused time advance to timers the subject waits for in handlers.
+    /// Delays before restarting failed actors don't make the subject busy,
+    /// so they aren't skipped this way.
+    async fn let_subject_run(&self) {
+        if self.is_subject_idle() {
             task::yield_now().await;
+        } else {
+            time::sleep(Duration::from_millis(1)).await;
         }
     }
 
@@ -118,6 +232,27 @@ impl Proxy {
         })
     }
 
+    /// Returns events emitted by the actor under test so far.
+    /// See `assert_logged!` to check them.
+    ///
+    /// Events emitted in tasks spawned by the actor aren't captured.
+    pub fn logs(&self) -> Vec<LogEvent> {
+        self.logs.lock().unwrap().clone()
+    }
+
+    /// Returns values of all metrics recorded by the actor under test so far.
+    /// Use `MetricsSnapshot::delta()` to get changes between two snapshots.
+    ///
+    /// Metrics recorded in tasks spawned by the actor aren't captured.
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.metrics.snapshot()
+    }
+
+    /// Returns a value of the metric, labels can be provided in any order.
+    pub fn metric(&self, name: &str, labels: &[(&str, &str)]) -> Option<MetricValue> {
+        self.metrics().get(name, labels).cloned()
+    }
+
     pub fn non_exhaustive(&mut self) {
         self.non_exhaustive = true;
     }
@@ -139,10 +274,14 @@ impl Proxy {
 
         Proxy {
             context,
+            subject: self.subject,
             meta: Arc::new(ObjectMeta {
                 group: "subproxy".into(),
                 key: None,
             }),
+            logs: self.logs.clone(),
+            metrics: self.metrics.clone(),
+            skipped: VecDeque::new(),
             non_exhaustive: self.non_exhaustive,
         }
     }
@@ -200,27 +339,27 @@ fn testers(tx: shared::OneshotSender<Context>) -> Schema {
 }
 
 pub async fn proxy(schema: Schema, config: impl for<'de> Deserializer<'de>) -> Proxy {
-    let _ = tracing_subscriber::fmt()
-        .with_target(false)
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .with_test_writer()
-        .try_init();
-
     let config = Value::deserialize(config).expect("invalid config");
     let mut map = BTreeMap::new();
-    map.insert(Value::String("subject".into()), config);
+    map.insert(Value::String(SUBJECT.into()), config);
     let config = Value::Map(map);
 
     let topology = Topology::empty();
-    let subject = topology.local("subject");
+
+    logs::install();
+    let logs = logs::capture(book_id(&topology));
+
+    recorder::install();
+    let metrics = recorder::capture(book_id(&topology));
+
+    let subject = topology.local(SUBJECT);
+    let subject_addr = subject.addrs()[0];
     let testers = topology.local("system.testers");
     let configurers = topology.local("system.configurers").entrypoint();
 
     testers.route_all_to(&subject);
     subject.route_all_to(&testers);
 
-    // TODO: capture log messages.
-    // TODO: capture metrics.
     configurers.mount(elfo_configurer::fixture(&topology, config));
     subject.mount(schema);
 
@@ -232,10 +371,14 @@ pub async fn proxy(schema: Schema, config: impl for<'de> Deserializer<'de>) -> P
 
     Proxy {
         context: rx.receive().await.unwrap(),
+        subject: subject_addr,
         meta: Arc::new(ObjectMeta {
             group: "proxy".into(),
             key: None,
         }),
+        logs,
+        metrics,
+        skipped: VecDeque::new(),
         non_exhaustive: false,
     }
 }
@@ -247,6 +390,7 @@ mod tests {
     use elfo_core as elfo;
     use elfo_core::{assert_msg_eq, config::AnyConfig};
     use elfo_macros::msg_raw as msg;
+    use tracing::Level;
 
     #[message(elfo = elfo_core)]
     #[derive(PartialEq)]
@@ -305,4 +449,285 @@ mod tests {
         subproxy.send(SomeMessage).await;
         assert_msg_eq!(subproxy.recv().await, SomeMessage2);
     }
+
+    #[tokio::test]
+    async fn sync_waits_for_busy_subject() {
+        let mut proxy = super::proxy(
+            ActorGroup::new().exec(|mut ctx| async move {
+                while let Some(envelope) = ctx.recv().await {
+                    msg!(match envelope {
+                        SomeMessage => {
+                            // More than any fixed number of yields in the proxy.
+                            for _ in 0..1000 {
+                                task::yield_now().await;
+                            }
+                            ctx.send(SomeMessage2).await.unwrap();
+                        }
+                    });
+                }
+            }),
+            AnyConfig::default(),
+        )
+        .await;
+
+        proxy.send(SomeMessage).await;
+        proxy.sync().await;
+        assert_msg_eq!(proxy.try_recv().expect("not synced"), SomeMessage2);
+    }
+
+    #[tokio::test]
+    async fn sync_handles_full_mailbox_of_proxy() {
+        const COUNT: usize = 100_010;
+
+        let mut proxy = super::proxy(
+            ActorGroup::new().exec(|mut ctx| async move {
+                while let Some(envelope) = ctx.recv().await {
+                    msg!(match envelope {
+                        SomeMessage => {
+                            for _ in 0..COUNT {
+                                ctx.send(SomeMessage2).await.unwrap();
+                            }
+                        }
+                    });
+                }
+            }),
+            AnyConfig::default(),
+        )
+        .await;
+
+        proxy.send(SomeMessage).await;
+        proxy.sync().await;
+
+        for _ in 0..COUNT {
+            assert_msg_eq!(proxy.try_recv().expect("not synced"), SomeMessage2);
+        }
+        assert!(proxy.try_recv().is_none());
+    }
+
+    #[tokio::test]
+    async fn sync_handles_failures_in_real_time() {
+        let mut proxy = super::proxy(
+            ActorGroup::new().exec(|mut ctx| async move {
+                while let Some(envelope) = ctx.recv().await {
+                    msg!(match envelope {
+                        SomeMessage => panic!("boom!"),
+                    });
+                }
+            }),
+            AnyConfig::default(),
+        )
+        .await;
+
+        proxy.send(SomeMessage).await;
+        let start = StdInstant::now();
+        proxy.sync().await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    async fn warner() -> Proxy {
+        super::proxy(
+            ActorGroup::new().exec(|mut ctx| async move {
+                while let Some(envelope) = ctx.recv().await {
+                    msg!(match envelope {
+                        SomeMessage => tracing::warn!(count = 5, name = "a", "something is wrong"),
+                    });
+                }
+            }),
+            AnyConfig::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn it_captures_logs() {
+        let mut proxy = warner().await;
+        proxy.send(SomeMessage).await;
+        proxy.sync().await;
+
+        crate::assert_logged!(proxy, WARN, contains = "is wrong", count = 5, name = "a");
+        assert!(proxy.logs().iter().all(|event| event.level != Level::ERROR));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn it_captures_logs_on_multi_thread_runtime() {
+        let mut proxy = warner().await;
+        let mut other = warner().await;
+        proxy.send(SomeMessage).await;
+        proxy.sync().await;
+
+        crate::assert_logged!(proxy, WARN, contains = "is wrong");
+        let warns = other
+            .logs()
+            .into_iter()
+            .filter(|event| event.level == Level::WARN);
+        assert_eq!(warns.count(), 0);
+        other.sync().await;
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "no event matches conditions")]
+    async fn it_panics_if_nothing_is_logged() {
+        let mut proxy = warner().await;
+        proxy.send(SomeMessage).await;
+        proxy.sync().await;
+
+        crate::assert_logged!(proxy, WARN, count = 6);
+    }
+
+    #[tokio::test]
+    async fn it_captures_metrics() {
+        let mut proxy = super::proxy(
+            ActorGroup::new().exec(|mut ctx| async move {
+                while let Some(envelope) = ctx.recv().await {
+                    msg!(match envelope {
+                        SomeMessage => {
+                            metrics::counter!("handled_total", "kind" => "some", "actor" => "a")
+                                .increment(2);
+                            metrics::gauge!("queue_size").set(3.);
+                            metrics::histogram!("latency").record(0.5);
+                        }
+                    });
+                }
+            }),
+            AnyConfig::default(),
+        )
+        .await;
+
+        assert_eq!(proxy.metric("handled_total", &[]), None);
+
+        proxy.send(SomeMessage).await;
+        proxy.sync().await;
+        let before = proxy.metrics();
+        assert_eq!(
+            before.counter("handled_total", &[("actor", "a"), ("kind", "some")]),
+            2
+        );
+
+        proxy.send(SomeMessage).await;
+        proxy.sync().await;
+        assert_eq!(
+            proxy.metric("handled_total", &[("kind", "some"), ("actor", "a")]),
+            Some(MetricValue::Counter(4))
+        );
+        assert_eq!(
+            proxy.metric("queue_size", &[]),
+            Some(MetricValue::Gauge(3.))
+        );
+
+        let delta = proxy.metrics().delta(&before);
+        assert_eq!(
+            delta.counter("handled_total", &[("kind", "some"), ("actor", "a")]),
+            2
+        );
+        assert_eq!(delta.histogram("latency", &[]), &[0.5]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn it_captures_metrics_on_multi_thread_runtime() {
+        let counter = || {
+            ActorGroup::new().exec(|mut ctx| async move {
+                while let Some(envelope) = ctx.recv().await {
+                    msg!(match envelope {
+                        SomeMessage => metrics::counter!("mt_handled_total").increment(1),
+                    });
+                }
+            })
+        };
+
+        let mut proxy = super::proxy(counter(), AnyConfig::default()).await;
+        let mut other = super::proxy(counter(), AnyConfig::default()).await;
+        proxy.send(SomeMessage).await;
+        proxy.sync().await;
+        other.sync().await;
+
+        assert_eq!(proxy.metrics().counter("mt_handled_total", &[]), 1);
+        assert_eq!(other.metrics().counter("mt_handled_total", &[]), 0);
+    }
+
+    async fn sleeper() -> Proxy {
+        super::proxy(
+            ActorGroup::new().exec(|mut ctx| async move {
+                while let Some(envelope) = ctx.recv().await {
+                    msg!(match envelope {
+                        SomeMessage => {
+                            time::sleep(Duration::from_secs(10)).await;
+                            ctx.send(SomeMessage2).await.unwrap();
+                        }
+                    });
+                }
+            }),
+            AnyConfig::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn recv_timeout_uses_tokio_time() {
+        time::pause();
+
+        let mut proxy = sleeper().await;
+        proxy.send(SomeMessage).await;
+        proxy.assert_no_messages_for(Duration::from_secs(9)).await;
+
+        let start = time::Instant::now();
+        let envelope = proxy.recv_timeout(Duration::from_secs(5)).await;
+        assert_msg_eq!(envelope.expect("timeout"), SomeMessage2);
+        assert_eq!(start.elapsed().as_secs(), 1);
+    }
+
+    #[tokio::test]
+    async fn sync_waits_for_sleeping_subject() {
+        time::pause();
+
+        let mut proxy = sleeper().await;
+        proxy.send(SomeMessage).await;
+
+        let start = time::Instant::now();
+        proxy.sync().await;
+        assert_msg_eq!(proxy.try_recv().expect("not synced"), SomeMessage2);
+        assert_eq!(start.elapsed().as_secs(), 10);
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "unexpected message")]
+    async fn assert_no_messages_for_panics_on_message() {
+        time::pause();
+
+        let mut proxy = sleeper().await;
+        proxy.send(SomeMessage).await;
+        proxy.assert_no_messages_for(Duration::from_secs(11)).await;
+    }
+
+    #[tokio::test]
+    async fn recv_matching_keeps_other_messages() {
+        let mut proxy = super::proxy(
+            ActorGroup::new().exec(|mut ctx| async move {
+                while let Some(envelope) = ctx.recv().await {
+                    msg!(match envelope {
+                        SomeMessage => {
+                            ctx.send(SomeMessage).await.unwrap();
+                            ctx.send(SomeMessage).await.unwrap();
+                            ctx.send(SomeMessage2).await.unwrap();
+                        }
+                    });
+                }
+            }),
+            AnyConfig::default(),
+        )
+        .await;
+
+        proxy.send(SomeMessage).await;
+        let envelope = proxy
+            .recv_matching(|envelope| {
+                msg!(match envelope {
+                    SomeMessage2 => true,
+                    _ => false,
+                })
+            })
+            .await;
+        assert_msg_eq!(envelope, SomeMessage2);
+        assert_msg_eq!(proxy.recv().await, SomeMessage);
+        assert_msg_eq!(proxy.try_recv().expect("skipped"), SomeMessage);
+        assert!(proxy.try_recv().is_none());
+    }
 }
@@ -0,0 +1,226 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex, Once, Weak,
+    },
+};
+
+use metrics::{
+    Counter, Gauge, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+};
+
+use elfo_core::{_priv::current_book_id, tls};
+
+use crate::proxy::SUBJECT;
+
+/// A metric name with sorted labels.
+type MetricKey = (String, Vec<(String, String)>);
+
+fn metric_key(name: &str, labels: &[(&str, &str)]) -> MetricKey {
+    let mut labels = labels
+        .iter()
+        .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+        .collect::<Vec<_>>();
+    labels.sort();
+    (name.into(), labels)
+}
+
+/// A value of a metric recorded by the actor under test.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricValue {
+    Counter(u64),
+    Gauge(f64),
+    /// All recorded samples in order.
+    Histogram(Vec<f64>),
+}
+
+/// Values of all metrics recorded by the actor under test.
+#[derive(Debug, Clone, Default)]
+pub struct MetricsSnapshot {
+    metrics: HashMap<MetricKey, MetricValue>,
+}
+
+impl MetricsSnapshot {
+    /// Labels can be provided in any order.
+    pub fn get(&self, name: &str, labels: &[(&str, &str)]) -> Option<&MetricValue> {
+        self.metrics.get(&metric_key(name, labels))
+    }
+
+    /// Returns `0` if the counter isn't registered.
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> u64 {
+        match self.get(name, labels) {
+            Some(MetricValue::Counter(value)) => *value,
+            _ => 0,
+        }
+    }
+
+    pub fn gauge(&self, name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+        match self.get(name, labels) {
+            Some(MetricValue::Gauge(value)) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Returns no samples if the histogram isn't registered.
+    pub fn histogram(&self, name: &str, labels: &[(&str, &str)]) -> &[f64] {
+        match self.get(name, labels) {
+            Some(MetricValue::Histogram(samples)) => samples,
+            _ => &[],
+        }
+    }
+
+    /// Returns changes since the `earlier` snapshot: counters are subtracted,
+    /// histograms contain only new samples and gauges are left as is.
+    pub fn delta(&self, earlier: &MetricsSnapshot) -> MetricsSnapshot {
+        let metrics = self
+            .metrics
+            .iter()
+            .map(|(key, value)| {
+                let value = match (value, earlier.metrics.get(key)) {
+                    (MetricValue::Counter(value), Some(MetricValue::Counter(prev))) => {
+                        MetricValue::Counter(value.saturating_sub(*prev))
+                    }
+                    (MetricValue::Histogram(samples), Some(MetricValue::Histogram(prev))) => {
+                        MetricValue::Histogram(samples.iter().skip(prev.len()).copied().collect())
+                    }
+                    (value, _) => value.clone(),
+                };
+                (key.clone(), value)
+            })
+            .collect();
+
+        MetricsSnapshot { metrics }
+    }
+}
+
+#[derive(Clone)]
+enum Handle {
+    Counter(Arc<AtomicU64>),
+    Gauge(Arc<AtomicU64>),
+    Histogram(Arc<Samples>),
+}
+
+#[derive(Default)]
+struct Samples(Mutex<Vec<f64>>);
+
+impl HistogramFn for Samples {
+    fn record(&self, value: f64) {
+        self.0.lock().unwrap().push(value);
+    }
+}
+
+/// Metrics recorded by the actor under test, one per proxy.
+#[derive(Default)]
+pub(crate) struct Storage {
+    handles: Mutex<HashMap<MetricKey, Handle>>,
+}
+
+impl Storage {
+    pub(crate) fn snapshot(&self) -> MetricsSnapshot {
+        let handles = self.handles.lock().unwrap();
+        let metrics = handles
+            .iter()
+            .map(|(key, handle)| {
+                let value = match handle {
+                    Handle::Counter(counter) => {
+                        MetricValue::Counter(counter.load(Ordering::Relaxed))
+                    }
+                    Handle::Gauge(gauge) => {
+                        MetricValue::Gauge(f64::from_bits(gauge.load(Ordering::Relaxed)))
+                    }
+                    Handle::Histogram(samples) => {
+                        MetricValue::Histogram(samples.0.lock().unwrap().clone())
+                    }
+                };
+                (key.clone(), value)
+            })
+            .collect();
+
+        MetricsSnapshot { metrics }
+    }
+
+    fn handle(&self, key: &Key, make: impl FnOnce() -> Handle) -> Handle {
+        let labels = key
+            .labels()
+            .map(|label| (label.key(), label.value()))
+            .collect::<Vec<_>>();
+
+        let mut handles = self.handles.lock().unwrap();
+        handles
+            .entry(metric_key(key.name(), &labels))
+            .or_insert_with(make)
+            .clone()
+    }
+}
+
+/// Metrics of proxies by ids of their topologies, forgotten with proxies.
+static STORAGES: Mutex<BTreeMap<usize, Weak<Storage>>> = Mutex::new(BTreeMap::new());
+
+/// Installs the global recorder. It's done once, so if another global recorder
+/// has been set, metrics aren't captured and it's warned about.
+pub(crate) fn install() {
+    static INSTALL: Once = Once::new();
+
+    INSTALL.call_once(|| {
+        if metrics::set_global_recorder(TestRecorder).is_err() {
+            eprintln!("WARNING: a global recorder is already set, `Proxy::metrics()` are empty");
+        }
+    });
+}
+
+/// Starts capturing metrics of the actor under test in the topology.
+pub(crate) fn capture(book_id: usize) -> Arc<Storage> {
+    let storage = Arc::new(Storage::default());
+    let mut stored = STORAGES.lock().unwrap();
+    stored.retain(|_, storage| storage.strong_count() > 0);
+    stored.insert(book_id, Arc::downgrade(&storage));
+    storage
+}
+
+fn register(key: &Key, make: impl FnOnce() -> Handle) -> Option<Handle> {
+    match tls::try_meta() {
+        Some(meta) if meta.group == SUBJECT => {}
+        _ => return None,
+    }
+
+    let book_id = current_book_id()?;
+    let storage = STORAGES.lock().unwrap().get(&book_id)?.upgrade()?;
+    Some(storage.handle(key, make))
+}
+
+/// Records metrics of the actor under test.
+///
+/// Like events, metrics are routed to the proxy by the topology of the actor.
+/// Handles are registered by every call of `counter!()` and others, so they
+/// are routed to the right proxy.
+struct TestRecorder;
+
+impl Recorder for TestRecorder {
+    fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+        match register(key, || Handle::Counter(Default::default())) {
+            Some(Handle::Counter(counter)) => Counter::from_arc(counter),
+            _ => Counter::noop(),
+        }
+    }
+
+    fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+        match register(key, || Handle::Gauge(Default::default())) {
+            Some(Handle::Gauge(gauge)) => Gauge::from_arc(gauge),
+            _ => Gauge::noop(),
+        }
+    }
+
+    fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+        match register(key, || Handle::Histogram(Default::default())) {
+            Some(Handle::Histogram(samples)) => Histogram::from_arc(samples),
+            _ => Histogram::noop(),
+        }
+    }
+}
@@ -297,5 +297,10 @@ fn topology() -> elfo::Topology {
 
 #[tokio::main]
 async fn main() {
-    elfo::start(topology()).await;
+    let topology = topology();
+
+    // Run with `--check-config <path>` to validate configs without starting.
+    elfo::configurer::check_config_by_args(&topology);
+
+    elfo::start(topology).await;
 }
@@ -0,0 +1,145 @@
+#![cfg(feature = "test-util")]
+
+use serde::Deserialize;
+use toml::toml;
+
+use elfo::{
+    messages::{ConfigUpdated, UpdateConfig},
+    prelude::*,
+    routers::{MapRouter, Outcome},
+};
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    capacity: u32,
+}
+
+#[message(ret = u32)]
+struct GetCapacity(String);
+
+#[message]
+struct Updated(String, u32);
+
+fn schema() -> Schema {
+    ActorGroup::new()
+        .config::<Config>()
+        .router(MapRouter::new(|envelope| {
+            msg!(match envelope {
+                GetCapacity(key) => Outcome::Unicast(key.clone()),
+                _ => Outcome::Default,
+            })
+        }))
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (GetCapacity(_), token) => {
+                        let capacity = ctx.config().capacity;
+                        ctx.respond(token, capacity);
+                    }
+                    ConfigUpdated => {
+                        let key = ctx.key().clone();
+                        let capacity = ctx.config().capacity;
+                        let _ = ctx.send(Updated(key, capacity)).await;
+                    }
+                    _ => {}
+                });
+            }
+        })
+}
+
+#[tokio::test]
+async fn it_applies_per_key_overrides() {
+    let config = toml! {
+        capacity = 10
+
+        [overrides.b]
+        capacity = 20
+    };
+
+    let proxy = elfo::test::proxy(schema(), config).await;
+
+    assert_eq!(proxy.request(GetCapacity("a".into())).await, 10);
+    assert_eq!(proxy.request(GetCapacity("b".into())).await, 20);
+}
+
+#[tokio::test]
+async fn it_updates_per_key_overrides() {
+    let config = toml! {
+        capacity = 10
+    };
+
+    let mut proxy = elfo::test::proxy(schema(), config).await;
+
+    assert_eq!(proxy.request(GetCapacity("a".into())).await, 10);
+    assert_eq!(proxy.request(GetCapacity("b".into())).await, 10);
+
+    let config = toml! {
+        capacity = 30
+
+        [overrides.b]
+        capacity = 40
+    };
+
+    let config = elfo::config::AnyConfig::deserialize(config).unwrap();
+    assert!(proxy.request(UpdateConfig::new(config)).await.is_ok());
+
+    let mut updated = vec![];
+    for _ in 0..2 {
+        msg!(match proxy.recv().await {
+            Updated(key, capacity) => updated.push((key, capacity)),
+            _ => panic!("unexpected message"),
+        });
+    }
+    updated.sort();
+    assert_eq!(updated, vec![("a".into(), 30), ("b".into(), 40)]);
+
+    assert_eq!(proxy.request(GetCapacity("a".into())).await, 30);
+    assert_eq!(proxy.request(GetCapacity("b".into())).await, 40);
+}
+
+#[tokio::test]
+async fn it_skips_actors_with_unchanged_configs() {
+    let config = toml! {
+        capacity = 10
+
+        [overrides.b]
+        capacity = 20
+    };
+
+    let mut proxy = elfo::test::proxy(schema(), config).await;
+
+    assert_eq!(proxy.request(GetCapacity("a".into())).await, 10);
+    assert_eq!(proxy.request(GetCapacity("b".into())).await, 20);
+
+    let config = toml! {
+        capacity = 10
+
+        [overrides.b]
+        capacity = 25
+    };
+
+    let config = elfo::config::AnyConfig::deserialize(config).unwrap();
+    let update = UpdateConfig::new(config.clone());
+    assert!(proxy.request(update).await.is_ok());
+
+    msg!(match proxy.recv().await {
+        Updated(key, capacity) => assert_eq!((key.as_str(), capacity), ("b", 25)),
+        _ => panic!("unexpected message"),
+    });
+    proxy.sync().await;
+    assert!(proxy.try_recv().is_none());
+
+    // Forced updates are sent to all actors.
+    let update = UpdateConfig::new(config).with_force(true);
+    assert!(proxy.request(update).await.is_ok());
+
+    let mut updated = vec![];
+    for _ in 0..2 {
+        msg!(match proxy.recv().await {
+            Updated(key, capacity) => updated.push((key, capacity)),
+            _ => panic!("unexpected message"),
+        });
+    }
+    updated.sort();
+    assert_eq!(updated, vec![("a".into(), 10), ("b".into(), 25)]);
+}
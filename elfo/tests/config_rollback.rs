@@ -0,0 +1,114 @@
+#![cfg(feature = "full")]
+
+use std::{fs, path::PathBuf};
+
+use futures::future;
+use serde::{de::Error as _, Deserialize, Deserializer};
+
+use elfo::{configurer::TryReloadConfigs, messages::ConfigUpdated, prelude::*, Addr, Topology};
+
+#[derive(Debug, Deserialize)]
+struct ProbeConfig {
+    value: u32,
+}
+
+#[message(ret = u32)]
+struct GetValue;
+
+fn probes() -> Schema {
+    ActorGroup::new()
+        .config::<ProbeConfig>()
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (GetValue, token) => {
+                        let value = ctx.config().value;
+                        ctx.respond(token, value);
+                    }
+                    ConfigUpdated => {}
+                })
+            }
+        })
+}
+
+/// Rejected if `reject` is set.
+#[derive(Debug)]
+struct PickyConfig;
+
+impl<'de> Deserialize<'de> for PickyConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Raw {
+            reject: bool,
+        }
+
+        if Raw::deserialize(deserializer)?.reject {
+            Err(D::Error::custom("the config is rejected"))
+        } else {
+            Ok(PickyConfig)
+        }
+    }
+}
+
+fn picky() -> Schema {
+    ActorGroup::new()
+        .config::<PickyConfig>()
+        .exec(|mut ctx| async move { while ctx.recv().await.is_some() {} })
+}
+
+fn addr_of(topology: &Topology, name: &str) -> Addr {
+    topology
+        .actor_groups()
+        .find(|group| group.name == name)
+        .map(|group| group.addr)
+        .unwrap()
+}
+
+fn write_config(path: &PathBuf, value: u32, reject: bool) {
+    let content = format!(
+        "[system.probes]\nvalue = {}\n[picky]\nreject = {}\n",
+        value, reject
+    );
+    fs::write(path, content).unwrap();
+}
+
+#[tokio::test]
+async fn it_rolls_back_system_groups_if_user_groups_fail() {
+    let path = std::env::temp_dir().join(format!("elfo-rollback-{}.toml", std::process::id()));
+    write_config(&path, 1, false);
+
+    let topology = Topology::empty();
+    let configurers = topology.local("system.configurers").entrypoint();
+    let probes = topology.local("system.probes");
+    let picky = topology.local("picky");
+
+    configurers.mount(elfo::configurer::from_path(&topology, &path));
+    probes.mount(self::probes());
+    picky.mount(self::picky());
+
+    let probes = addr_of(&topology, "system.probes");
+    let configurers = addr_of(&topology, "system.configurers");
+
+    elfo::_priv::do_start(topology, |ctx| async move {
+        let value = ctx.request(GetValue).from(probes).resolve().await;
+        assert_eq!(value.unwrap(), 1);
+
+        // System groups are updated first, then `picky` rejects its config.
+        write_config(&path, 2, true);
+        let reloaded = ctx.request(TryReloadConfigs::default()).from(configurers);
+        let rejected = reloaded.resolve().await.unwrap().unwrap_err();
+
+        assert_eq!(rejected.errors.len(), 1);
+        assert_eq!(rejected.errors[0].group.as_deref(), Some("picky"));
+        assert_eq!(rejected.rolled_back, vec!["system.probes"]);
+        assert!(rejected.inconsistent.is_empty());
+
+        let value = ctx.request(GetValue).from(probes).resolve().await;
+        assert_eq!(value.unwrap(), 1);
+
+        let _ = fs::remove_file(&path);
+        future::ready(()).await
+    })
+    .await
+    .unwrap();
+}
@@ -0,0 +1,53 @@
+#![cfg(feature = "schema")]
+
+use schemars::JsonSchema;
+use serde::Deserialize;
+use serde_json::json;
+
+use elfo::{config::Secret, prelude::*, Topology};
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize, JsonSchema)]
+struct Config {
+    /// The maximum number of items.
+    capacity: u32,
+    token: Secret<String>,
+}
+
+fn group(with_schema: bool) -> Schema {
+    let group = ActorGroup::new().config::<Config>();
+    let group = if with_schema {
+        group.with_config_schema()
+    } else {
+        group
+    };
+    group.exec(|_ctx| async {})
+}
+
+#[test]
+fn it_combines_schemas_by_group_paths() {
+    let topology = Topology::empty();
+    topology.local("producers").mount(group(true));
+    topology.local("system.producers").mount(group(true));
+    topology.local("consumers").mount(group(false));
+
+    let schema = serde_json::to_value(topology.config_schema()).unwrap();
+
+    assert_eq!(schema["type"], "object");
+    assert_eq!(
+        schema["properties"]["producers"]["$ref"],
+        "#/definitions/Config"
+    );
+    assert_eq!(
+        schema["properties"]["system"]["properties"]["producers"]["$ref"],
+        "#/definitions/Config"
+    );
+    assert!(schema["properties"].get("consumers").is_none());
+
+    let config = &schema["definitions"]["Config"];
+    assert_eq!(
+        config["properties"]["capacity"]["description"],
+        "The maximum number of items."
+    );
+    assert_eq!(config["properties"]["token"], json!({ "type": "string" }));
+}
@@ -0,0 +1,86 @@
+#![cfg(feature = "full")]
+
+use std::{fs, path::PathBuf};
+
+use futures::future;
+use serde::Deserialize;
+
+use elfo::{
+    configurer::TryReloadConfigs,
+    messages::{ConfigUpdated, Ping},
+    prelude::*,
+    Addr, Topology,
+};
+
+#[derive(Debug, Deserialize)]
+struct Config {
+    capacity: u32,
+}
+
+#[message(ret = u32)]
+struct GetCapacity;
+
+fn producers() -> Schema {
+    ActorGroup::new()
+        .config::<Config>()
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    (Ping, token) => ctx.respond(token, ()),
+                    (GetCapacity, token) => {
+                        let capacity = ctx.config().capacity;
+                        ctx.respond(token, capacity);
+                    }
+                    ConfigUpdated => {}
+                })
+            }
+        })
+}
+
+fn addr_of(topology: &Topology, name: &str) -> Addr {
+    topology
+        .actor_groups()
+        .find(|group| group.name == name)
+        .map(|group| group.addr)
+        .unwrap()
+}
+
+fn write_config(path: &PathBuf, capacity: u32) {
+    let content = format!("[producers]\ncapacity = {}\n", capacity);
+    fs::write(path, content).unwrap();
+}
+
+#[tokio::test]
+async fn it_configures_entrypoints() {
+    let path = std::env::temp_dir().join(format!("elfo-entrypoints-{}.toml", std::process::id()));
+    write_config(&path, 10);
+
+    let topology = Topology::empty();
+    let producers = topology.local("producers").entrypoint();
+    let configurers = topology.local("system.configurers").entrypoint();
+
+    configurers.mount(elfo::configurer::from_path(&topology, &path));
+    producers.mount(self::producers());
+
+    let producers = addr_of(&topology, "producers");
+    let configurers = addr_of(&topology, "system.configurers");
+
+    elfo::_priv::do_start(topology, |ctx| async move {
+        // The config is available at startup.
+        let capacity = ctx.request(GetCapacity).from(producers).resolve().await;
+        assert_eq!(capacity.unwrap(), 10);
+
+        // And it's updated on reloading.
+        write_config(&path, 20);
+        let reloaded = ctx.request(TryReloadConfigs::default()).from(configurers);
+        assert!(reloaded.resolve().await.unwrap().is_ok());
+
+        let capacity = ctx.request(GetCapacity).from(producers).resolve().await;
+        assert_eq!(capacity.unwrap(), 20);
+
+        let _ = fs::remove_file(&path);
+        future::ready(()).await
+    })
+    .await
+    .unwrap();
+}
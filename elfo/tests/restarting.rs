@@ -1,4 +1,5 @@
 #![cfg(feature = "test-util")]
+#![allow(clippy::never_loop)] // Actors fail or terminate on the first message.
 
 use std::{panic::AssertUnwindSafe, time::Duration};
 
@@ -55,6 +56,9 @@ async fn it_restarts_with_timeout_after_failures() {
             .await;
         assert!(r.is_err());
 
-        tokio::time::advance(Duration::from_secs(5)).await;
+        // Timers have a millisecond granularity.
+        tokio::time::advance(Duration::from_secs(5) + Duration::from_millis(1)).await;
+        // The actor is restarted by another task.
+        proxy.sync().await;
     }
 }
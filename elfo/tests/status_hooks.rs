@@ -0,0 +1,39 @@
+#![cfg(feature = "test-util")]
+
+use std::sync::{Arc, Mutex};
+
+use elfo::{config::AnyConfig, prelude::*, ActorStatus};
+
+#[message]
+struct Alarm;
+
+#[tokio::test]
+async fn it_calls_hooks_with_actors_meta() {
+    let statuses = Arc::new(Mutex::new(Vec::new()));
+    let statuses1 = statuses.clone();
+
+    let schema = ActorGroup::new()
+        .on_status_changed(move |meta, status| {
+            let status = status.to_string();
+            statuses1.lock().unwrap().push((meta.group.clone(), status));
+        })
+        .exec(|mut ctx| async move {
+            while let Some(envelope) = ctx.recv().await {
+                msg!(match envelope {
+                    Alarm => ctx.set_status(ActorStatus::ALARMING.with_details("oops")),
+                });
+            }
+        });
+
+    let mut proxy = elfo::test::proxy(schema, AnyConfig::default()).await;
+    proxy.send(Alarm).await;
+    proxy.sync().await;
+
+    let statuses = statuses.lock().unwrap();
+    let subject = statuses
+        .iter()
+        .filter(|(group, _)| group == "subject")
+        .map(|(_, status)| status.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(subject, vec!["Normal", "Alarming: oops"]);
+}